@@ -37,8 +37,11 @@ where
     T: DynamicConfigs,
 {
     pub fn rabbitmq_uri(&self) -> String {
+        let scheme = if self.rabbitmq.tls { "amqps" } else { "amqp" };
+
         format!(
-            "amqp://{}:{}@{}:{}{}",
+            "{}://{}:{}@{}:{}{}",
+            scheme,
             self.rabbitmq.user,
             self.rabbitmq.password,
             self.rabbitmq.host,
@@ -75,4 +78,14 @@ mod tests {
             )
         )
     }
+
+    #[test]
+    fn should_return_amqps_uri_when_tls_is_enabled() {
+        let mut cfg = Configs::<Empty>::default();
+        cfg.rabbitmq.tls = true;
+        cfg.rabbitmq.port = 5671;
+
+        assert!(cfg.rabbitmq_uri().starts_with("amqps://"));
+        assert!(cfg.rabbitmq_uri().contains(":5671"));
+    }
 }
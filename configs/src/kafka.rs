@@ -1,12 +1,70 @@
+use std::collections::HashMap;
+
 #[derive(Debug, Clone)]
 pub struct KafkaConfigs {
+    ///Used when `brokers` is empty. Default: localhost
     pub host: String,
+    ///Default: 9094
     pub port: u64,
+    ///Comma separated `host:port` bootstrap servers
+    pub brokers: String,
+    ///Milliseconds. Default: 6000
     pub timeout: u64,
+    ///PLAINTEXT, SSL, SASL_PLAINTEXT or SASL_SSL. Default: SASL_SSL
     pub security_protocol: String,
+    ///PLAIN, SCRAM-SHA-256 or SCRAM-SHA-512, only used with SASL. Default: PLAIN
     pub sasl_mechanisms: String,
     pub user: String,
     pub password: String,
+    ///PEM CA bundle. Default: system root certificates
+    pub ssl_ca_path: String,
+    ///PEM client certificate
+    pub ssl_cert_path: String,
+    ///PEM client private key
+    pub ssl_key_path: String,
+    pub ssl_key_password: String,
+    ///Consumer group. Default: app name
+    pub group_id: String,
+    ///Where a consumer group without committed offsets starts: earliest or latest. Default: latest
+    pub auto_offset_reset: String,
+    ///none, gzip, snappy, lz4 or zstd. Default: none
+    pub compression: String,
+    ///Milliseconds the producer waits to batch records. Default: 5
+    pub linger_ms: u64,
+    ///Maximum bytes of a producer batch. Default: 1000000
+    pub batch_size: u64,
+    ///0, 1 or all, idempotent producers always use all. Default: 1
+    pub acks: String,
+    ///librdkafka properties set as-is, overriding the ones above
+    pub properties: HashMap<String, String>,
+}
+
+impl KafkaConfigs {
+    pub fn bootstrap_servers(&self) -> String {
+        if self.brokers.is_empty() {
+            return format!("{}:{}", self.host, self.port);
+        }
+
+        self.brokers.clone()
+    }
+
+    pub fn uses_sasl(&self) -> bool {
+        self.security_protocol.to_uppercase().starts_with("SASL")
+    }
+
+    pub fn uses_ssl(&self) -> bool {
+        self.security_protocol.to_uppercase().ends_with("SSL")
+    }
+
+    ///Parses `key=value` pairs separated by `;`
+    pub fn parse_properties(value: &str) -> HashMap<String, String> {
+        value
+            .split(';')
+            .filter_map(|pair| pair.split_once('='))
+            .map(|(key, value)| (key.trim().to_owned(), value.trim().to_owned()))
+            .filter(|(key, _)| !key.is_empty())
+            .collect()
+    }
 }
 
 impl Default for KafkaConfigs {
@@ -14,11 +72,54 @@ impl Default for KafkaConfigs {
         Self {
             host: "localhost".into(),
             port: 9094,
+            brokers: Default::default(),
             timeout: 6000,
             security_protocol: "SASL_SSL".into(),
             sasl_mechanisms: "PLAIN".into(),
             user: Default::default(),
             password: Default::default(),
+            ssl_ca_path: Default::default(),
+            ssl_cert_path: Default::default(),
+            ssl_key_path: Default::default(),
+            ssl_key_password: Default::default(),
+            group_id: Default::default(),
+            auto_offset_reset: "latest".into(),
+            compression: "none".into(),
+            linger_ms: 5,
+            batch_size: 1000000,
+            acks: "1".into(),
+            properties: HashMap::new(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_resolve_bootstrap_servers_and_security() {
+        let mut cfg = KafkaConfigs::default();
+        assert_eq!(cfg.bootstrap_servers(), "localhost:9094");
+        assert!(cfg.uses_sasl() && cfg.uses_ssl());
+
+        cfg.brokers = "kafka-1:9092,kafka-2:9092".into();
+        cfg.security_protocol = "plaintext".into();
+        assert_eq!(cfg.bootstrap_servers(), "kafka-1:9092,kafka-2:9092");
+        assert!(!cfg.uses_sasl() && !cfg.uses_ssl());
+    }
+
+    #[test]
+    fn should_parse_properties() {
+        let props = KafkaConfigs::parse_properties(
+            "fetch.min.bytes=1; socket.keepalive.enable=true;;invalid",
+        );
+
+        assert_eq!(props.len(), 2);
+        assert_eq!(props.get("fetch.min.bytes"), Some(&"1".to_owned()));
+        assert_eq!(
+            props.get("socket.keepalive.enable"),
+            Some(&"true".to_owned())
+        );
+    }
+}
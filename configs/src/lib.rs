@@ -23,9 +23,9 @@ pub use health_readiness::HealthReadinessConfigs;
 pub use identity_server::IdentityServerConfigs;
 pub use kafka::KafkaConfigs;
 pub use metrics::{MetricConfigs, MetricExporterKind};
-pub use mqtt::{MQTTBrokerKind, MQTTConfigs, MQTTTransport};
+pub use mqtt::{MQTTBrokerKind, MQTTConfigs, MQTTProtocolVersion, MQTTTransport};
 pub use postgres::PostgresConfigs;
-pub use rabbitmq::RabbitMQConfigs;
+pub use rabbitmq::{RabbitMQAuthMechanism, RabbitMQConfigs, DEFAULT_CHANNEL_POOL_SIZE};
 pub use secrets::SecretsManagerKind;
 pub use sqlite::SqliteConfigs;
 pub use traces::{TraceConfigs, TraceExporterKind};
@@ -63,6 +63,37 @@ impl Display for MQTTTransport {
     }
 }
 
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum MQTTProtocolVersion {
+    #[default]
+    V311,
+    V5,
+}
+
+impl From<&str> for MQTTProtocolVersion {
+    fn from(value: &str) -> Self {
+        match value.to_uppercase().as_str() {
+            "5" | "5.0" | "V5" => MQTTProtocolVersion::V5,
+            _ => MQTTProtocolVersion::V311,
+        }
+    }
+}
+
+impl From<&String> for MQTTProtocolVersion {
+    fn from(value: &String) -> Self {
+        MQTTProtocolVersion::from(value.as_str())
+    }
+}
+
+impl Display for MQTTProtocolVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MQTTProtocolVersion::V311 => write!(f, "3.1.1"),
+            MQTTProtocolVersion::V5 => write!(f, "5"),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct MQTTConfigs {
     pub broker_kind: MQTTBrokerKind,
@@ -84,6 +115,10 @@ pub struct MQTTConfigs {
     pub cert_path: String,
     ///Used with Public Cloud Brokers
     pub private_key_path: String,
+    ///Default: 3.1.1
+    pub protocol_version: MQTTProtocolVersion,
+    ///Topic aliases accepted from the broker, only used with MQTT v5. Default: 0
+    pub topic_alias_maximum: u16,
 }
 
 impl Default for MQTTConfigs {
@@ -99,6 +134,8 @@ impl Default for MQTTConfigs {
             root_ca_path: Default::default(),
             cert_path: Default::default(),
             private_key_path: Default::default(),
+            protocol_version: MQTTProtocolVersion::default(),
+            topic_alias_maximum: 0,
         }
     }
 }
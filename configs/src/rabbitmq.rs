@@ -1,3 +1,40 @@
+use std::fmt::Display;
+
+///Default number of channels in the publisher pool
+pub const DEFAULT_CHANNEL_POOL_SIZE: usize = 10;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum RabbitMQAuthMechanism {
+    #[default]
+    Plain,
+    ///Authenticates with the TLS client certificate
+    External,
+}
+
+impl From<&str> for RabbitMQAuthMechanism {
+    fn from(value: &str) -> Self {
+        match value.to_uppercase().as_str() {
+            "EXTERNAL" => RabbitMQAuthMechanism::External,
+            _ => RabbitMQAuthMechanism::Plain,
+        }
+    }
+}
+
+impl From<&String> for RabbitMQAuthMechanism {
+    fn from(value: &String) -> Self {
+        RabbitMQAuthMechanism::from(value.as_str())
+    }
+}
+
+impl Display for RabbitMQAuthMechanism {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RabbitMQAuthMechanism::Plain => write!(f, "plain"),
+            RabbitMQAuthMechanism::External => write!(f, "external"),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct RabbitMQConfigs {
     ///Default: localhost
@@ -9,6 +46,26 @@ pub struct RabbitMQConfigs {
     /// Default: guest
     pub password: String,
     pub vhost: String,
+    ///Default: `DEFAULT_CHANNEL_POOL_SIZE`
+    pub channel_pool_size: usize,
+    ///Connects using amqps. Default: false
+    pub tls: bool,
+    ///PEM CA bundle. Default: system root certificates
+    pub tls_ca_cert_path: String,
+    ///PEM client certificate used with certificate authentication
+    pub tls_cert_path: String,
+    ///PEM client private key used with certificate authentication
+    pub tls_private_key_path: String,
+    ///Default: true
+    pub tls_verify: bool,
+    ///SNI and expected certificate name. Default: host
+    pub tls_server_name: String,
+    ///Default: plain
+    pub auth_mechanism: RabbitMQAuthMechanism,
+    ///Seconds, 0 disables heartbeats. Default: 60
+    pub heartbeat: u16,
+    ///Milliseconds. Default: 30000
+    pub connection_timeout: u64,
 }
 
 impl Default for RabbitMQConfigs {
@@ -19,6 +76,16 @@ impl Default for RabbitMQConfigs {
             user: "default".to_owned(),
             password: "default".to_owned(),
             vhost: Default::default(),
+            channel_pool_size: DEFAULT_CHANNEL_POOL_SIZE,
+            tls: false,
+            tls_ca_cert_path: Default::default(),
+            tls_cert_path: Default::default(),
+            tls_private_key_path: Default::default(),
+            tls_verify: true,
+            tls_server_name: Default::default(),
+            auth_mechanism: RabbitMQAuthMechanism::default(),
+            heartbeat: 60,
+            connection_timeout: 30000,
         }
     }
 }
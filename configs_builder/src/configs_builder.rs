@@ -7,28 +7,39 @@ use crate::{
         HEALTH_READINESS_PORT_ENV_KEY, HOST_NAME_ENV_KEY, IDENTITY_SERVER_AUDIENCE_ENV_KEY,
         IDENTITY_SERVER_CLIENT_ID_ENV_KEY, IDENTITY_SERVER_CLIENT_SECRET_ENV_KEY,
         IDENTITY_SERVER_GRANT_TYPE_ENV_KEY, IDENTITY_SERVER_ISSUER_ENV_KEY,
-        IDENTITY_SERVER_REALM_ENV_KEY, IDENTITY_SERVER_URL_ENV_KEY, KAFKA_HOST_ENV_KEY,
-        KAFKA_PASSWORD_ENV_KEY, KAFKA_PORT_ENV_KEY, KAFKA_SASL_MECHANISMS_ENV_KEY,
-        KAFKA_SECURITY_PROTOCOL_ENV_KEY, KAFKA_TIMEOUT_ENV_KEY, KAFKA_USER_ENV_KEY,
-        LOCAL_ENV_FILE_NAME, LOG_LEVEL_ENV_KEY, METRIC_ACCESS_KEY_ENV_KEY, METRIC_EXPORTER_ENV_KEY,
+        IDENTITY_SERVER_REALM_ENV_KEY, IDENTITY_SERVER_URL_ENV_KEY, KAFKA_ACKS_ENV_KEY,
+        KAFKA_AUTO_OFFSET_RESET_ENV_KEY, KAFKA_BATCH_SIZE_ENV_KEY, KAFKA_BROKERS_ENV_KEY,
+        KAFKA_COMPRESSION_ENV_KEY, KAFKA_GROUP_ID_ENV_KEY, KAFKA_HOST_ENV_KEY,
+        KAFKA_LINGER_MS_ENV_KEY, KAFKA_PASSWORD_ENV_KEY, KAFKA_PORT_ENV_KEY,
+        KAFKA_PROPERTIES_ENV_KEY, KAFKA_SASL_MECHANISMS_ENV_KEY, KAFKA_SECURITY_PROTOCOL_ENV_KEY,
+        KAFKA_SSL_CA_PATH_ENV_KEY, KAFKA_SSL_CERT_PATH_ENV_KEY, KAFKA_SSL_KEY_PASSWORD_ENV_KEY,
+        KAFKA_SSL_KEY_PATH_ENV_KEY, KAFKA_TIMEOUT_ENV_KEY, KAFKA_USER_ENV_KEY, LOCAL_ENV_FILE_NAME,
+        LOG_LEVEL_ENV_KEY, METRIC_ACCESS_KEY_ENV_KEY, METRIC_EXPORTER_ENV_KEY,
         METRIC_EXPORT_RATE_BASE_ENV_KEY, METRIC_EXPORT_TIMEOUT_ENV_KEY,
         METRIC_HEADER_ACCESS_KEY_ENV_KEY, METRIC_HOST_ENV_KEY, METRIC_SERVICE_TYPE_ENV_KEY,
         MQTT_BROKER_KIND_ENV_KEY, MQTT_CA_CERT_PATH_ENV_KEY, MQTT_HOST_ENV_KEY,
-        MQTT_PASSWORD_ENV_KEY, MQTT_PORT_ENV_KEY, MQTT_TRANSPORT_ENV_KEY, MQTT_USER_ENV_KEY,
+        MQTT_PASSWORD_ENV_KEY, MQTT_PORT_ENV_KEY, MQTT_PROTOCOL_VERSION_ENV_KEY,
+        MQTT_TOPIC_ALIAS_MAXIMUM_ENV_KEY, MQTT_TRANSPORT_ENV_KEY, MQTT_USER_ENV_KEY,
         POSTGRES_DB_ENV_KEY, POSTGRES_HOST_ENV_KEY, POSTGRES_PASSWORD_ENV_KEY,
-        POSTGRES_PORT_ENV_KEY, POSTGRES_USER_ENV_KEY, PROD_FILE_NAME, RABBITMQ_HOST_ENV_KEY,
-        RABBITMQ_PASSWORD_ENV_KEY, RABBITMQ_PORT_ENV_KEY, RABBITMQ_USER_ENV_KEY,
-        RABBITMQ_VHOST_ENV_KEY, SECRET_KEY_ENV_KEY, SECRET_MANAGER_ENV_KEY, SECRET_PREFIX,
-        SQLITE_FILE_NAME_ENV_KEY, STAGING_FILE_NAME, TRACE_ACCESS_KEY_ENV_KEY,
-        TRACE_EXPORTER_ENV_KEY, TRACE_EXPORT_RATE_BASE_ENV_KEY, TRACE_EXPORT_TIMEOUT_ENV_KEY,
+        POSTGRES_PORT_ENV_KEY, POSTGRES_USER_ENV_KEY, PROD_FILE_NAME,
+        RABBITMQ_AUTH_MECHANISM_ENV_KEY, RABBITMQ_CHANNEL_POOL_SIZE_ENV_KEY,
+        RABBITMQ_CONNECTION_TIMEOUT_ENV_KEY, RABBITMQ_HEARTBEAT_ENV_KEY, RABBITMQ_HOST_ENV_KEY,
+        RABBITMQ_PASSWORD_ENV_KEY, RABBITMQ_PORT_ENV_KEY, RABBITMQ_TLS_CA_CERT_PATH_ENV_KEY,
+        RABBITMQ_TLS_CERT_PATH_ENV_KEY, RABBITMQ_TLS_ENV_KEY,
+        RABBITMQ_TLS_PRIVATE_KEY_PATH_ENV_KEY, RABBITMQ_TLS_SERVER_NAME_ENV_KEY,
+        RABBITMQ_TLS_VERIFY_ENV_KEY, RABBITMQ_USER_ENV_KEY, RABBITMQ_VHOST_ENV_KEY,
+        SECRET_KEY_ENV_KEY, SECRET_MANAGER_ENV_KEY, SECRET_PREFIX, SQLITE_FILE_NAME_ENV_KEY,
+        STAGING_FILE_NAME, TRACE_ACCESS_KEY_ENV_KEY, TRACE_EXPORTER_ENV_KEY,
+        TRACE_EXPORT_RATE_BASE_ENV_KEY, TRACE_EXPORT_TIMEOUT_ENV_KEY,
         TRACE_HEADER_ACCESS_KEY_ENV_KEY, TRACE_HOST_ENV_KEY, TRACE_SERVICE_TYPE_ENV_KEY,
     },
     errors::ConfigsError,
 };
 use base64::{engine::general_purpose, Engine};
 use configs::{
-    AppConfigs, Configs, DynamicConfigs, Environment, MQTTBrokerKind, MQTTTransport,
-    MetricExporterKind, SecretsManagerKind, TraceExporterKind,
+    AppConfigs, Configs, DynamicConfigs, Environment, KafkaConfigs, MQTTBrokerKind,
+    MQTTProtocolVersion, MQTTTransport, MetricExporterKind, RabbitMQAuthMechanism,
+    SecretsManagerKind, TraceExporterKind, DEFAULT_CHANNEL_POOL_SIZE,
 };
 use dotenvy::from_filename;
 use secrets_manager::{AWSSecretClientBuilder, FakeSecretClient, SecretClient};
@@ -414,6 +425,15 @@ impl ConfigBuilder {
                 cfg.mqtt.root_ca_path = self.get_from_secret(value.into(), "".into());
                 true
             }
+            MQTT_PROTOCOL_VERSION_ENV_KEY if self.mqtt => {
+                let version = self.get_from_secret::<String>(value.into(), "3.1.1".into());
+                cfg.mqtt.protocol_version = MQTTProtocolVersion::from(&version);
+                true
+            }
+            MQTT_TOPIC_ALIAS_MAXIMUM_ENV_KEY if self.mqtt => {
+                cfg.mqtt.topic_alias_maximum = self.get_from_secret(value.into(), 0);
+                true
+            }
             _ => false,
         }
     }
@@ -448,6 +468,48 @@ impl ConfigBuilder {
                 cfg.rabbitmq.vhost = self.get_from_secret(value.into(), "".into());
                 true
             }
+            RABBITMQ_CHANNEL_POOL_SIZE_ENV_KEY if self.rabbitmq => {
+                cfg.rabbitmq.channel_pool_size =
+                    self.get_from_secret(value.into(), DEFAULT_CHANNEL_POOL_SIZE);
+                true
+            }
+            RABBITMQ_TLS_ENV_KEY if self.rabbitmq => {
+                cfg.rabbitmq.tls = self.get_from_secret(value.into(), false);
+                true
+            }
+            RABBITMQ_TLS_CA_CERT_PATH_ENV_KEY if self.rabbitmq => {
+                cfg.rabbitmq.tls_ca_cert_path = self.get_from_secret(value.into(), "".into());
+                true
+            }
+            RABBITMQ_TLS_CERT_PATH_ENV_KEY if self.rabbitmq => {
+                cfg.rabbitmq.tls_cert_path = self.get_from_secret(value.into(), "".into());
+                true
+            }
+            RABBITMQ_TLS_PRIVATE_KEY_PATH_ENV_KEY if self.rabbitmq => {
+                cfg.rabbitmq.tls_private_key_path = self.get_from_secret(value.into(), "".into());
+                true
+            }
+            RABBITMQ_TLS_VERIFY_ENV_KEY if self.rabbitmq => {
+                cfg.rabbitmq.tls_verify = self.get_from_secret(value.into(), true);
+                true
+            }
+            RABBITMQ_TLS_SERVER_NAME_ENV_KEY if self.rabbitmq => {
+                cfg.rabbitmq.tls_server_name = self.get_from_secret(value.into(), "".into());
+                true
+            }
+            RABBITMQ_AUTH_MECHANISM_ENV_KEY if self.rabbitmq => {
+                let mechanism = self.get_from_secret::<String>(value.into(), "plain".into());
+                cfg.rabbitmq.auth_mechanism = RabbitMQAuthMechanism::from(&mechanism);
+                true
+            }
+            RABBITMQ_HEARTBEAT_ENV_KEY if self.rabbitmq => {
+                cfg.rabbitmq.heartbeat = self.get_from_secret(value.into(), 60);
+                true
+            }
+            RABBITMQ_CONNECTION_TIMEOUT_ENV_KEY if self.rabbitmq => {
+                cfg.rabbitmq.connection_timeout = self.get_from_secret(value.into(), 30000);
+                true
+            }
             _ => false,
         }
     }
@@ -490,6 +552,55 @@ impl ConfigBuilder {
                 cfg.kafka.password = self.get_from_secret(value.into(), "password".into());
                 true
             }
+            KAFKA_GROUP_ID_ENV_KEY if self.kafka => {
+                cfg.kafka.group_id = self.get_from_secret(value.into(), "".into());
+                true
+            }
+            KAFKA_BROKERS_ENV_KEY if self.kafka => {
+                cfg.kafka.brokers = self.get_from_secret(value.into(), "".into());
+                true
+            }
+            KAFKA_SSL_CA_PATH_ENV_KEY if self.kafka => {
+                cfg.kafka.ssl_ca_path = self.get_from_secret(value.into(), "".into());
+                true
+            }
+            KAFKA_SSL_CERT_PATH_ENV_KEY if self.kafka => {
+                cfg.kafka.ssl_cert_path = self.get_from_secret(value.into(), "".into());
+                true
+            }
+            KAFKA_SSL_KEY_PATH_ENV_KEY if self.kafka => {
+                cfg.kafka.ssl_key_path = self.get_from_secret(value.into(), "".into());
+                true
+            }
+            KAFKA_SSL_KEY_PASSWORD_ENV_KEY if self.kafka => {
+                cfg.kafka.ssl_key_password = self.get_from_secret(value.into(), "".into());
+                true
+            }
+            KAFKA_AUTO_OFFSET_RESET_ENV_KEY if self.kafka => {
+                cfg.kafka.auto_offset_reset = self.get_from_secret(value.into(), "latest".into());
+                true
+            }
+            KAFKA_COMPRESSION_ENV_KEY if self.kafka => {
+                cfg.kafka.compression = self.get_from_secret(value.into(), "none".into());
+                true
+            }
+            KAFKA_LINGER_MS_ENV_KEY if self.kafka => {
+                cfg.kafka.linger_ms = self.get_from_secret(value.into(), 5);
+                true
+            }
+            KAFKA_BATCH_SIZE_ENV_KEY if self.kafka => {
+                cfg.kafka.batch_size = self.get_from_secret(value.into(), 1000000);
+                true
+            }
+            KAFKA_ACKS_ENV_KEY if self.kafka => {
+                cfg.kafka.acks = self.get_from_secret(value.into(), "1".into());
+                true
+            }
+            KAFKA_PROPERTIES_ENV_KEY if self.kafka => {
+                let properties = self.get_from_secret::<String>(value.into(), "".into());
+                cfg.kafka.properties = KafkaConfigs::parse_properties(&properties);
+                true
+            }
             _ => false,
         }
     }
@@ -30,12 +30,24 @@ pub const MQTT_PORT_ENV_KEY: &str = "MQTT_PORT";
 pub const MQTT_USER_ENV_KEY: &str = "MQTT_USER";
 pub const MQTT_PASSWORD_ENV_KEY: &str = "MQTT_PASSWORD";
 pub const MQTT_CA_CERT_PATH_ENV_KEY: &str = "MQTT_CA_CERT_PATH";
+pub const MQTT_PROTOCOL_VERSION_ENV_KEY: &str = "MQTT_PROTOCOL_VERSION";
+pub const MQTT_TOPIC_ALIAS_MAXIMUM_ENV_KEY: &str = "MQTT_TOPIC_ALIAS_MAXIMUM";
 
 pub const RABBITMQ_HOST_ENV_KEY: &str = "RABBITMQ_HOST";
 pub const RABBITMQ_PORT_ENV_KEY: &str = "RABBITMQ_PORT";
 pub const RABBITMQ_USER_ENV_KEY: &str = "RABBITMQ_USER";
 pub const RABBITMQ_PASSWORD_ENV_KEY: &str = "RABBITMQ_PASSWORD";
 pub const RABBITMQ_VHOST_ENV_KEY: &str = "AMQP_VHOST";
+pub const RABBITMQ_CHANNEL_POOL_SIZE_ENV_KEY: &str = "RABBITMQ_CHANNEL_POOL_SIZE";
+pub const RABBITMQ_TLS_ENV_KEY: &str = "RABBITMQ_TLS";
+pub const RABBITMQ_TLS_CA_CERT_PATH_ENV_KEY: &str = "RABBITMQ_TLS_CA_CERT_PATH";
+pub const RABBITMQ_TLS_CERT_PATH_ENV_KEY: &str = "RABBITMQ_TLS_CERT_PATH";
+pub const RABBITMQ_TLS_PRIVATE_KEY_PATH_ENV_KEY: &str = "RABBITMQ_TLS_PRIVATE_KEY_PATH";
+pub const RABBITMQ_TLS_VERIFY_ENV_KEY: &str = "RABBITMQ_TLS_VERIFY";
+pub const RABBITMQ_TLS_SERVER_NAME_ENV_KEY: &str = "RABBITMQ_TLS_SERVER_NAME";
+pub const RABBITMQ_AUTH_MECHANISM_ENV_KEY: &str = "RABBITMQ_AUTH_MECHANISM";
+pub const RABBITMQ_HEARTBEAT_ENV_KEY: &str = "RABBITMQ_HEARTBEAT";
+pub const RABBITMQ_CONNECTION_TIMEOUT_ENV_KEY: &str = "RABBITMQ_CONNECTION_TIMEOUT";
 
 pub const KAFKA_HOST_ENV_KEY: &str = "KAFKA_HOST";
 pub const KAFKA_PORT_ENV_KEY: &str = "KAFKA_PORT";
@@ -44,6 +56,19 @@ pub const KAFKA_SECURITY_PROTOCOL_ENV_KEY: &str = "KAFKA_SECURITY_PROTOCOL";
 pub const KAFKA_SASL_MECHANISMS_ENV_KEY: &str = "KAFKA_SASL_MECHANISMS";
 pub const KAFKA_USER_ENV_KEY: &str = "KAFKA_USER";
 pub const KAFKA_PASSWORD_ENV_KEY: &str = "KAFKA_PASSWORD";
+pub const KAFKA_GROUP_ID_ENV_KEY: &str = "KAFKA_GROUP_ID";
+pub const KAFKA_BROKERS_ENV_KEY: &str = "KAFKA_BROKERS";
+pub const KAFKA_SSL_CA_PATH_ENV_KEY: &str = "KAFKA_SSL_CA_PATH";
+pub const KAFKA_SSL_CERT_PATH_ENV_KEY: &str = "KAFKA_SSL_CERT_PATH";
+pub const KAFKA_SSL_KEY_PATH_ENV_KEY: &str = "KAFKA_SSL_KEY_PATH";
+pub const KAFKA_SSL_KEY_PASSWORD_ENV_KEY: &str = "KAFKA_SSL_KEY_PASSWORD";
+pub const KAFKA_AUTO_OFFSET_RESET_ENV_KEY: &str = "KAFKA_AUTO_OFFSET_RESET";
+pub const KAFKA_COMPRESSION_ENV_KEY: &str = "KAFKA_COMPRESSION";
+pub const KAFKA_LINGER_MS_ENV_KEY: &str = "KAFKA_LINGER_MS";
+pub const KAFKA_BATCH_SIZE_ENV_KEY: &str = "KAFKA_BATCH_SIZE";
+pub const KAFKA_ACKS_ENV_KEY: &str = "KAFKA_ACKS";
+///`key=value` librdkafka properties separated by `;`
+pub const KAFKA_PROPERTIES_ENV_KEY: &str = "KAFKA_PROPERTIES";
 
 pub const ENABLE_TRACES_ENV_KEY: &str = "ENABLE_TRACES";
 pub const TRACE_EXPORTER_ENV_KEY: &str = "TRACE_EXPORTER";
@@ -0,0 +1,73 @@
+use async_trait::async_trait;
+use configs::Empty;
+use configs_builder::ConfigBuilder;
+use kafka::{dispatcher::KafkaDispatcher, replay::SeekPosition, routing::MsgTypeStrategy};
+use messaging::{
+    dispatcher::{Dispatcher, DispatcherDefinition},
+    errors::MessagingError,
+    handler::{ConsumerHandler, ConsumerMessage},
+};
+use opentelemetry::Context;
+use std::{env, error::Error, sync::Arc};
+use tracing::info;
+
+const USAGE: &str = "usage: kafka-replay <topic> <from> [to] - positions: beginning, end, offset:<offset>, time:<unix ms>";
+
+struct PrintConsumer;
+
+#[async_trait]
+impl ConsumerHandler for PrintConsumer {
+    async fn exec(&self, _ctx: &Context, msg: &ConsumerMessage) -> Result<(), MessagingError> {
+        println!(
+            "{}\t{}\t{}",
+            msg.from,
+            msg.offset.unwrap_or_default(),
+            String::from_utf8_lossy(&msg.data)
+        );
+
+        Ok(())
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let (topic, from, to) = match args.as_slice() {
+        [topic, from] => (topic, position(from)?, SeekPosition::End),
+        [topic, from, to] => (topic, position(from)?, position(to)?),
+        _ => return Err(USAGE.into()),
+    };
+
+    let cfgs = ConfigBuilder::new().kafka().build::<Empty>().await?;
+
+    // every record of the topic is routed to the same handler
+    let dispatcher = KafkaDispatcher::new(&cfgs)?
+        .msg_type_strategy(MsgTypeStrategy::Topic)
+        .register(
+            &DispatcherDefinition::new(topic.as_str(), topic.as_str()),
+            Arc::new(PrintConsumer),
+        );
+
+    let summary = dispatcher.replay(from, to).await?;
+
+    info!(
+        records = summary.records,
+        failures = summary.failures,
+        "replay finished"
+    );
+
+    Ok(())
+}
+
+fn position(value: &str) -> Result<SeekPosition, Box<dyn Error>> {
+    let position = match value.split_once(':') {
+        None if value == "beginning" => SeekPosition::Beginning,
+        None if value == "end" => SeekPosition::End,
+        Some(("offset", offset)) => SeekPosition::Offset(offset.parse()?),
+        Some(("time", timestamp)) => SeekPosition::Timestamp(timestamp.parse()?),
+        _ => return Err(format!("invalid position `{}` - {}", value, USAGE).into()),
+    };
+
+    Ok(position)
+}
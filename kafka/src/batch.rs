@@ -0,0 +1,88 @@
+use rdkafka::{Message, Offset, TopicPartitionList};
+use std::{collections::BTreeMap, time::Duration};
+
+///Default maximum number of records delivered to a `BatchConsumerHandler`
+pub const DEFAULT_BATCH_SIZE: usize = 500;
+
+///Default time waited for a batch to fill up after its first record
+pub const DEFAULT_BATCH_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// First and last offset of each partition of a batch
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct BatchRange {
+    partitions: BTreeMap<(String, i32), (i64, i64)>,
+}
+
+impl BatchRange {
+    pub(crate) fn new<M: Message>(records: &[M]) -> BatchRange {
+        let mut partitions = BTreeMap::new();
+
+        for received in records {
+            partitions
+                .entry((received.topic().to_owned(), received.partition()))
+                .and_modify(|(first, last): &mut (i64, i64)| {
+                    *first = (*first).min(received.offset());
+                    *last = (*last).max(received.offset());
+                })
+                .or_insert((received.offset(), received.offset()));
+        }
+
+        BatchRange { partitions }
+    }
+
+    ///Offsets committed once the whole batch is handled
+    pub(crate) fn commit_positions(&self) -> TopicPartitionList {
+        let mut tpl = TopicPartitionList::new();
+
+        for ((topic, partition), (_, last)) in &self.partitions {
+            let _ = tpl.add_partition_offset(topic, *partition, Offset::Offset(last + 1));
+        }
+
+        tpl
+    }
+
+    ///Offsets the partitions are moved back to when the batch fails
+    pub(crate) fn rewind_positions(&self) -> Vec<(&str, i32, i64)> {
+        self.partitions
+            .iter()
+            .map(|((topic, partition), (first, _))| (topic.as_str(), *partition, *first))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rdkafka::{message::OwnedMessage, Timestamp};
+
+    fn record(partition: i32, offset: i64) -> OwnedMessage {
+        OwnedMessage::new(
+            None,
+            None,
+            "events".to_owned(),
+            Timestamp::NotAvailable,
+            partition,
+            offset,
+            None,
+        )
+    }
+
+    #[test]
+    fn should_compute_batch_positions() {
+        let range = BatchRange::new(&[record(0, 7), record(1, 3), record(0, 8), record(1, 4)]);
+
+        let commit = range.commit_positions();
+        assert_eq!(
+            commit.find_partition("events", 0).map(|e| e.offset()),
+            Some(Offset::Offset(9))
+        );
+        assert_eq!(
+            commit.find_partition("events", 1).map(|e| e.offset()),
+            Some(Offset::Offset(5))
+        );
+        assert_eq!(
+            range.rewind_positions(),
+            vec![("events", 0, 7), ("events", 1, 3)]
+        );
+    }
+}
@@ -0,0 +1,398 @@
+use crate::dispatcher::RecordProcessor;
+use opentelemetry::global;
+use rdkafka::{message::OwnedMessage, Message, Offset, TopicPartitionList};
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeSet, HashMap},
+    hash::{Hash, Hasher},
+};
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tracing::error;
+
+///Default number of records of a partition being handled before it is paused
+pub const DEFAULT_MAX_IN_FLIGHT: usize = 100;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Concurrency {
+    ///One record at a time across all the partitions
+    #[default]
+    Sequential,
+    ///One worker per partition, records are handled in partition order
+    Partition,
+    ///`N` workers shared by all the partitions, records with the same key are handled in order by the same worker
+    Key(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum WorkerId {
+    Partition(String, i32),
+    Key(usize),
+}
+
+impl Concurrency {
+    pub(crate) fn worker_id<M: Message>(&self, received: &M) -> WorkerId {
+        match self {
+            Concurrency::Key(workers) => {
+                let mut hasher = DefaultHasher::new();
+                match received.key() {
+                    Some(key) => key.hash(&mut hasher),
+                    None => received.partition().hash(&mut hasher),
+                }
+
+                WorkerId::Key((hasher.finish() % (*workers).max(1) as u64) as usize)
+            }
+            _ => WorkerId::Partition(received.topic().to_owned(), received.partition()),
+        }
+    }
+
+    ///Records sharing the same ordering key are handled in offset order
+    pub(crate) fn ordering_key<M: Message>(&self, received: &M) -> OrderingKey {
+        let key = match self {
+            Concurrency::Key(_) => received.key().map(|k| k.to_vec()),
+            _ => None,
+        };
+
+        (received.topic().to_owned(), received.partition(), key)
+    }
+}
+
+pub(crate) type OrderingKey = (String, i32, Option<Vec<u8>>);
+
+///Record sent to a worker, along with the generation of its partition
+#[derive(Debug)]
+pub(crate) struct Queued {
+    pub(crate) received: OwnedMessage,
+    pub(crate) generation: u64,
+}
+
+///Record handled by a worker
+#[derive(Debug)]
+pub(crate) struct Completed {
+    pub(crate) topic: String,
+    pub(crate) partition: i32,
+    pub(crate) offset: i64,
+    ///The record failed and could not be sent to the retry/dead-letter topics, or it was
+    ///held back behind such a record
+    pub(crate) failed: bool,
+}
+
+/// Starts a worker handling the records sent to the returned channel in order.
+/// The worker stops once the channel is dropped and the queued records are handled.
+pub(crate) fn spawn_worker(
+    processor: RecordProcessor,
+    concurrency: Concurrency,
+    done: UnboundedSender<Completed>,
+) -> UnboundedSender<Queued> {
+    let (tx, mut rx) = mpsc::unbounded_channel::<Queued>();
+
+    tokio::spawn(async move {
+        let tracer = global::tracer("kafka-worker");
+        let mut failed_keys = FailedKeys::default();
+
+        while let Some(Queued {
+            received,
+            generation,
+        }) = rx.recv().await
+        {
+            let key = concurrency.ordering_key(&received);
+
+            let failed = if failed_keys.holds_back(&key, generation) {
+                true
+            } else {
+                let failed = match processor.dispatch(&tracer, &received).await {
+                    Err(err) => processor.handle_failure(&received, &err).await.is_err(),
+                    Ok(()) => false,
+                };
+
+                if failed {
+                    failed_keys.insert(key, generation);
+                }
+                failed
+            };
+
+            if done
+                .send(Completed {
+                    topic: received.topic().to_owned(),
+                    partition: received.partition(),
+                    offset: received.offset(),
+                    failed,
+                })
+                .is_err()
+            {
+                error!("dispatcher stopped, dropping worker");
+                break;
+            }
+        }
+    });
+
+    tx
+}
+
+/// Keys of the records that failed in the current generation of their partition. The
+/// later records of these keys are held back, so they are handled in order once the
+/// partition starts over from the failed record.
+#[derive(Debug, Default)]
+pub(crate) struct FailedKeys {
+    keys: HashMap<OrderingKey, u64>,
+}
+
+impl FailedKeys {
+    pub(crate) fn insert(&mut self, key: OrderingKey, generation: u64) {
+        self.keys.insert(key, generation);
+    }
+
+    ///Forgets the failures of the previous generations of the partition
+    pub(crate) fn holds_back(&mut self, key: &OrderingKey, generation: u64) -> bool {
+        self.keys.retain(|(topic, partition, _), g| {
+            topic != &key.0 || *partition != key.1 || *g == generation
+        });
+
+        self.keys.contains_key(key)
+    }
+}
+
+#[derive(Debug, Default)]
+struct PartitionOffsets {
+    pending: BTreeSet<i64>,
+    ///Pending records that could not be retried nor dead-lettered
+    failed: BTreeSet<i64>,
+    ///Records handled above the oldest pending one, skipped when consumed again
+    handled: BTreeSet<i64>,
+    highest: i64,
+    committed: i64,
+}
+
+/// Offsets handled out of order per partition, only the position below the oldest
+/// pending record is committed. The generation of a partition changes each time it
+/// is rewound or revoked.
+#[derive(Debug, Default)]
+pub(crate) struct OffsetTracker {
+    partitions: HashMap<(String, i32), PartitionOffsets>,
+    generations: HashMap<(String, i32), u64>,
+}
+
+impl OffsetTracker {
+    pub(crate) fn track(&mut self, topic: &str, partition: i32, offset: i64) {
+        self.partitions
+            .entry((topic.to_owned(), partition))
+            .or_default()
+            .pending
+            .insert(offset);
+    }
+
+    pub(crate) fn in_flight(&self, topic: &str, partition: i32) -> usize {
+        self.partitions
+            .get(&(topic.to_owned(), partition))
+            .map_or(0, |p| p.pending.len())
+    }
+
+    pub(crate) fn generation(&self, topic: &str, partition: i32) -> u64 {
+        self.generations
+            .get(&(topic.to_owned(), partition))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    ///Whether the record was already handled before the partition was rewound
+    pub(crate) fn handled(&self, topic: &str, partition: i32, offset: i64) -> bool {
+        self.partitions
+            .get(&(topic.to_owned(), partition))
+            .is_some_and(|p| p.handled.contains(&offset))
+    }
+
+    ///Stops tracking a revoked partition, later completions are not committed
+    pub(crate) fn forget(&mut self, topic: &str, partition: i32) {
+        self.partitions.remove(&(topic.to_owned(), partition));
+        *self
+            .generations
+            .entry((topic.to_owned(), partition))
+            .or_default() += 1;
+    }
+
+    ///Offset to commit when the position of the partition moved forward
+    pub(crate) fn complete(&mut self, topic: &str, partition: i32, offset: i64) -> Option<i64> {
+        let p = self.partitions.get_mut(&(topic.to_owned(), partition))?;
+
+        p.pending.remove(&offset);
+        p.highest = p.highest.max(offset + 1);
+
+        let position = p.pending.first().copied().unwrap_or(p.highest);
+        p.handled.insert(offset);
+        p.handled = p.handled.split_off(&position);
+
+        if position <= p.committed {
+            return None;
+        }
+
+        p.committed = position;
+        Some(position)
+    }
+
+    ///Keeps the record pending, so the position never moves past it until it is consumed again
+    pub(crate) fn fail(&mut self, topic: &str, partition: i32, offset: i64) {
+        if let Some(p) = self.partitions.get_mut(&(topic.to_owned(), partition)) {
+            p.failed.insert(offset);
+        }
+    }
+
+    pub(crate) fn has_failed(&self, topic: &str, partition: i32) -> bool {
+        self.partitions
+            .get(&(topic.to_owned(), partition))
+            .is_some_and(|p| !p.failed.is_empty())
+    }
+
+    ///Offset to consume again once the only pending records of the partition are the failed
+    ///ones, the partition starts over from it
+    pub(crate) fn rewind(&mut self, topic: &str, partition: i32) -> Option<i64> {
+        let p = self.partitions.get_mut(&(topic.to_owned(), partition))?;
+
+        if p.failed.is_empty() || p.pending.len() > p.failed.len() {
+            return None;
+        }
+
+        let offset = p.failed.first().copied()?;
+        p.pending.clear();
+        p.failed.clear();
+        p.highest = offset;
+
+        *self
+            .generations
+            .entry((topic.to_owned(), partition))
+            .or_default() += 1;
+
+        Some(offset)
+    }
+
+    ///Committed positions of every tracked partition
+    pub(crate) fn positions(&self) -> TopicPartitionList {
+        let mut tpl = TopicPartitionList::new();
+
+        for ((topic, partition), p) in &self.partitions {
+            if p.committed > 0 {
+                let _ = tpl.add_partition_offset(topic, *partition, Offset::Offset(p.committed));
+            }
+        }
+
+        tpl
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rdkafka::Timestamp;
+
+    fn record(key: &str, partition: i32) -> OwnedMessage {
+        record_at(key, partition, 0)
+    }
+
+    fn record_at(key: &str, partition: i32, offset: i64) -> OwnedMessage {
+        OwnedMessage::new(
+            None,
+            Some(key.as_bytes().to_vec()),
+            "orders".to_owned(),
+            Timestamp::NotAvailable,
+            partition,
+            offset,
+            None,
+        )
+    }
+
+    #[test]
+    fn should_assign_workers() {
+        let by_key = Concurrency::Key(4);
+        assert_eq!(
+            by_key.worker_id(&record("order-1", 0)),
+            by_key.worker_id(&record("order-1", 1))
+        );
+
+        let by_partition = Concurrency::Partition;
+        assert_eq!(
+            by_partition.worker_id(&record("order-1", 1)),
+            WorkerId::Partition("orders".to_owned(), 1)
+        );
+    }
+
+    #[test]
+    fn should_commit_below_oldest_pending_offset() {
+        let mut tracker = OffsetTracker::default();
+        tracker.track("orders", 0, 10);
+        tracker.track("orders", 0, 11);
+        tracker.track("orders", 0, 12);
+
+        assert_eq!(tracker.complete("orders", 0, 11), Some(10));
+        assert_eq!(tracker.in_flight("orders", 0), 2);
+        assert_eq!(tracker.complete("orders", 0, 10), Some(12));
+        assert_eq!(tracker.complete("orders", 0, 12), Some(13));
+        assert_eq!(tracker.complete("orders", 1, 0), None);
+    }
+
+    #[test]
+    fn should_not_commit_past_failed_offset() {
+        let mut tracker = OffsetTracker::default();
+        tracker.track("orders", 0, 10);
+        tracker.track("orders", 0, 11);
+        tracker.track("orders", 0, 12);
+
+        tracker.fail("orders", 0, 10);
+        assert!(tracker.has_failed("orders", 0));
+        assert_eq!(tracker.complete("orders", 0, 11), Some(10));
+        assert_eq!(tracker.rewind("orders", 0), None);
+
+        assert_eq!(tracker.complete("orders", 0, 12), None);
+        assert_eq!(tracker.rewind("orders", 0), Some(10));
+        assert!(!tracker.has_failed("orders", 0));
+
+        tracker.track("orders", 0, 10);
+        assert_eq!(tracker.complete("orders", 0, 10), Some(11));
+    }
+
+    #[test]
+    fn should_keep_key_order_across_rewind() {
+        let concurrency = Concurrency::Key(1);
+        let mut tracker = OffsetTracker::default();
+        let mut failed_keys = FailedKeys::default();
+        let mut handled = vec![];
+
+        let records = [
+            record_at("order-1", 0, 10),
+            record_at("order-2", 0, 11),
+            record_at("order-1", 0, 12),
+        ];
+        let generation = tracker.generation("orders", 0);
+
+        // order-1 can not be handled the first time, order-2 is
+        for received in &records {
+            tracker.track("orders", 0, received.offset());
+            let key = concurrency.ordering_key(received);
+
+            if failed_keys.holds_back(&key, generation) {
+                tracker.fail("orders", 0, received.offset());
+            } else if received.offset() == 10 {
+                failed_keys.insert(key, generation);
+                tracker.fail("orders", 0, received.offset());
+            } else {
+                handled.push(received.offset());
+                tracker.complete("orders", 0, received.offset());
+            }
+        }
+        assert_eq!(handled, vec![11]);
+        assert_eq!(tracker.rewind("orders", 0), Some(10));
+
+        let generation = tracker.generation("orders", 0);
+        let mut position = None;
+        for received in &records {
+            if tracker.handled("orders", 0, received.offset()) {
+                position = tracker.complete("orders", 0, received.offset());
+                continue;
+            }
+
+            tracker.track("orders", 0, received.offset());
+            assert!(!failed_keys.holds_back(&concurrency.ordering_key(received), generation));
+            handled.push(received.offset());
+            position = tracker.complete("orders", 0, received.offset());
+        }
+
+        assert_eq!(handled, vec![11, 10, 12]);
+        assert_eq!(position, Some(13));
+    }
+}
@@ -0,0 +1,92 @@
+use configs::{Configs, DynamicConfigs, Environment};
+use messaging::errors::MessagingError;
+use rdkafka::{
+    admin::AdminClient, client::DefaultClientContext, config::RDKafkaLogLevel, ClientConfig,
+};
+use std::sync::Arc;
+use tracing::error;
+
+/// Brokers, client id, security and log level shared by the producers, consumers and admin clients
+pub fn client_config<T>(cfgs: &Configs<T>) -> ClientConfig
+where
+    T: DynamicConfigs,
+{
+    let log_level = match cfgs.app.env {
+        Environment::Local | Environment::Dev => RDKafkaLogLevel::Debug,
+        Environment::Staging | Environment::Prod => RDKafkaLogLevel::Info,
+    };
+
+    let mut config = ClientConfig::new();
+    config
+        .set("bootstrap.servers", cfgs.kafka.bootstrap_servers())
+        .set("client.id", cfgs.app.name.clone())
+        .set("security.protocol", cfgs.kafka.security_protocol.clone())
+        .set_log_level(log_level);
+
+    if cfgs.kafka.uses_sasl() {
+        config
+            .set("sasl.mechanism", cfgs.kafka.sasl_mechanisms.clone())
+            .set("sasl.username", cfgs.kafka.user.clone())
+            .set("sasl.password", cfgs.kafka.password.clone());
+    }
+
+    if cfgs.kafka.uses_ssl() {
+        for (key, value) in [
+            ("ssl.ca.location", &cfgs.kafka.ssl_ca_path),
+            ("ssl.certificate.location", &cfgs.kafka.ssl_cert_path),
+            ("ssl.key.location", &cfgs.kafka.ssl_key_path),
+            ("ssl.key.password", &cfgs.kafka.ssl_key_password),
+        ] {
+            if !value.is_empty() {
+                config.set(key, value.clone());
+            }
+        }
+    }
+
+    config
+}
+
+/// `client_config` with the producer batching and compression
+pub fn producer_config<T>(cfgs: &Configs<T>) -> ClientConfig
+where
+    T: DynamicConfigs,
+{
+    client_config(cfgs)
+        .set("compression.type", cfgs.kafka.compression.clone())
+        .set("linger.ms", cfgs.kafka.linger_ms.to_string())
+        .set("batch.size", cfgs.kafka.batch_size.to_string())
+        .set("message.timeout.ms", cfgs.kafka.timeout.to_string())
+        .to_owned()
+}
+
+///Applies the passthrough properties, must be the last change before creating the client
+pub(crate) fn with_properties<T>(config: &mut ClientConfig, cfgs: &Configs<T>) -> ClientConfig
+where
+    T: DynamicConfigs,
+{
+    for (key, value) in &cfgs.kafka.properties {
+        config.set(key, value);
+    }
+
+    config.to_owned()
+}
+
+pub fn new_admin_client<T>(
+    cfgs: &Configs<T>,
+) -> Result<Arc<AdminClient<DefaultClientContext>>, MessagingError>
+where
+    T: DynamicConfigs,
+{
+    match with_properties(&mut client_config(cfgs), cfgs)
+        .create::<AdminClient<DefaultClientContext>>()
+    {
+        Ok(admin) => Ok(Arc::new(admin)),
+        Err(err) => {
+            error!(
+                error = err.to_string(),
+                "failure to create kafka admin client"
+            );
+            Err(MessagingError::ConnectionError {})
+        }
+    }
+}
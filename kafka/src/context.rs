@@ -0,0 +1,253 @@
+use opentelemetry::{
+    global,
+    metrics::{Counter, Gauge},
+    KeyValue,
+};
+use rdkafka::{
+    consumer::{ConsumerContext, Rebalance},
+    error::KafkaResult,
+    statistics::Statistics,
+    ClientContext, Offset, TopicPartitionList,
+};
+use std::sync::{Arc, Mutex, RwLock};
+use tracing::{debug, error, warn};
+
+///Interval of the librdkafka statistics used by the consumer metrics
+pub const DEFAULT_STATISTICS_INTERVAL_MS: u64 = 5000;
+
+/// Partition assignment changes of the consumer group. The callbacks run inside the
+/// consumer poll, so they must return quickly.
+///
+/// Delivery is at-least-once across rebalances: the dispatcher does not commit nor wait
+/// for the records being handled when partitions are revoked. Only the offsets already
+/// committed asynchronously are kept, the records of the revoked partitions handled
+/// after the last successful commit are consumed again by their new owner.
+pub trait RebalanceListener: Send + Sync {
+    ///Called once the partitions are assigned to this consumer
+    fn on_assigned(&self, _partitions: &[(String, i32)]) {}
+    ///Called before the partitions are taken away, records of these partitions may
+    ///still be in flight and their offsets are not committed
+    fn on_revoked(&self, _partitions: &[(String, i32)]) {}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum RebalanceEvent {
+    Assigned(Vec<(String, i32)>),
+    Revoked(Vec<(String, i32)>),
+}
+
+struct ConsumerMetrics {
+    lag: Gauge<i64>,
+    committed_offset: Gauge<i64>,
+    rebalances: Counter<u64>,
+    reply_queue: Gauge<i64>,
+    received_messages: Gauge<i64>,
+    broker_rtt: Gauge<i64>,
+}
+
+/// `ConsumerContext` of the `KafkaDispatcher`: forwards the rebalances to the dispatcher
+/// and the `RebalanceListener`, and records the consumer statistics as metrics.
+pub struct DispatcherContext {
+    listener: RwLock<Option<Arc<dyn RebalanceListener>>>,
+    events: Mutex<Vec<RebalanceEvent>>,
+    metrics: ConsumerMetrics,
+}
+
+impl DispatcherContext {
+    pub(crate) fn new() -> DispatcherContext {
+        let meter = global::meter("kafka-consumer");
+
+        DispatcherContext {
+            listener: RwLock::new(None),
+            events: Mutex::new(vec![]),
+            metrics: ConsumerMetrics {
+                lag: meter
+                    .i64_gauge("kafka.consumer.lag")
+                    .with_description(
+                        "Messages between the high watermark and the committed offset",
+                    )
+                    .init(),
+                committed_offset: meter.i64_gauge("kafka.consumer.committed_offset").init(),
+                rebalances: meter.u64_counter("kafka.consumer.rebalances").init(),
+                reply_queue: meter
+                    .i64_gauge("kafka.client.reply_queue")
+                    .with_description("Events waiting to be served by the client")
+                    .init(),
+                received_messages: meter.i64_gauge("kafka.client.received_messages").init(),
+                broker_rtt: meter
+                    .i64_gauge("kafka.broker.rtt")
+                    .with_description("Average broker round-trip time in microseconds")
+                    .init(),
+            },
+        }
+    }
+
+    pub(crate) fn set_listener(&self, listener: Arc<dyn RebalanceListener>) {
+        if let Ok(mut current) = self.listener.write() {
+            *current = Some(listener);
+        }
+    }
+
+    ///Rebalances since the last call, in the order they happened
+    pub(crate) fn take_events(&self) -> Vec<RebalanceEvent> {
+        match self.events.lock() {
+            Ok(mut events) => events.drain(..).collect(),
+            Err(_) => vec![],
+        }
+    }
+
+    fn push(&self, event: RebalanceEvent) {
+        if let Ok(mut events) = self.events.lock() {
+            events.push(event);
+        }
+    }
+
+    fn listener(&self) -> Option<Arc<dyn RebalanceListener>> {
+        self.listener.read().ok().and_then(|l| l.clone())
+    }
+}
+
+impl ClientContext for DispatcherContext {
+    fn stats(&self, statistics: Statistics) {
+        let client = [KeyValue::new("client", statistics.client_id.clone())];
+
+        self.metrics.reply_queue.record(statistics.replyq, &client);
+        self.metrics
+            .received_messages
+            .record(statistics.rxmsgs, &client);
+
+        for (name, broker) in &statistics.brokers {
+            if let Some(rtt) = &broker.rtt {
+                self.metrics
+                    .broker_rtt
+                    .record(rtt.avg, &[KeyValue::new("broker", name.clone())]);
+            }
+        }
+
+        for (topic, stats) in &statistics.topics {
+            for (partition, p) in &stats.partitions {
+                // -1 is the internal unassigned partition, negative lags are not known yet
+                if *partition < 0 || p.consumer_lag < 0 {
+                    continue;
+                }
+
+                self.metrics.lag.record(
+                    p.consumer_lag,
+                    &[
+                        KeyValue::new("topic", topic.clone()),
+                        KeyValue::new("partition", *partition as i64),
+                    ],
+                );
+            }
+        }
+    }
+}
+
+impl ConsumerContext for DispatcherContext {
+    fn pre_rebalance(&self, rebalance: &Rebalance<'_>) {
+        match rebalance {
+            Rebalance::Revoke(tpl) => {
+                let partitions = partitions(tpl);
+                debug!(partitions = partitions.len(), "partitions revoked");
+
+                self.metrics
+                    .rebalances
+                    .add(1, &[KeyValue::new("type", "revoke")]);
+
+                if let Some(listener) = self.listener() {
+                    listener.on_revoked(&partitions);
+                }
+
+                self.push(RebalanceEvent::Revoked(partitions));
+            }
+            Rebalance::Error(err) => {
+                error!(error = err.to_string(), "consumer group rebalance failure");
+            }
+            Rebalance::Assign(_) => {}
+        }
+    }
+
+    fn post_rebalance(&self, rebalance: &Rebalance<'_>) {
+        if let Rebalance::Assign(tpl) = rebalance {
+            let partitions = partitions(tpl);
+            debug!(partitions = partitions.len(), "partitions assigned");
+
+            self.metrics
+                .rebalances
+                .add(1, &[KeyValue::new("type", "assign")]);
+
+            if let Some(listener) = self.listener() {
+                listener.on_assigned(&partitions);
+            }
+
+            self.push(RebalanceEvent::Assigned(partitions));
+        }
+    }
+
+    fn commit_callback(&self, result: KafkaResult<()>, offsets: &TopicPartitionList) {
+        if let Err(err) = result {
+            warn!(error = err.to_string(), "failure to commit offsets");
+            return;
+        }
+
+        for elem in offsets.elements() {
+            if let Offset::Offset(offset) = elem.offset() {
+                self.metrics.committed_offset.record(
+                    offset,
+                    &[
+                        KeyValue::new("topic", elem.topic().to_owned()),
+                        KeyValue::new("partition", elem.partition() as i64),
+                    ],
+                );
+            }
+        }
+    }
+}
+
+fn partitions(tpl: &TopicPartitionList) -> Vec<(String, i32)> {
+    tpl.elements()
+        .iter()
+        .map(|elem| (elem.topic().to_owned(), elem.partition()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Default)]
+    struct CountingListener {
+        revoked: AtomicUsize,
+    }
+
+    impl RebalanceListener for CountingListener {
+        fn on_revoked(&self, partitions: &[(String, i32)]) {
+            self.revoked.fetch_add(partitions.len(), Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn should_forward_rebalances() {
+        let ctx = DispatcherContext::new();
+        let listener = Arc::new(CountingListener::default());
+        ctx.set_listener(listener.clone());
+
+        let mut tpl = TopicPartitionList::new();
+        tpl.add_partition("orders", 0);
+        tpl.add_partition("orders", 1);
+
+        ctx.pre_rebalance(&Rebalance::Revoke(&tpl));
+        ctx.post_rebalance(&Rebalance::Assign(&tpl));
+
+        assert_eq!(listener.revoked.load(Ordering::SeqCst), 2);
+        assert_eq!(
+            ctx.take_events(),
+            vec![
+                RebalanceEvent::Revoked(vec![("orders".to_owned(), 0), ("orders".to_owned(), 1)]),
+                RebalanceEvent::Assigned(vec![("orders".to_owned(), 0), ("orders".to_owned(), 1)]),
+            ]
+        );
+        assert!(ctx.take_events().is_empty());
+    }
+}
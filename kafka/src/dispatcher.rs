@@ -1,53 +1,129 @@
 use async_trait::async_trait;
-use configs::{Configs, DynamicConfigs, Environment};
+use configs::{Configs, DynamicConfigs};
 use messaging::{
     dispatcher::{Dispatcher, DispatcherDefinition},
     errors::MessagingError,
-    handler::{ConsumerHandler, ConsumerMessage},
+    handler::{BatchConsumerHandler, ConsumerHandler, ConsumerMessage},
 };
 use opentelemetry::{
     global::{self, BoxedTracer},
     Context,
 };
 use rdkafka::{
-    consumer::StreamConsumer,
-    message::{BorrowedHeaders, Headers},
-    ClientConfig, Message,
+    consumer::{CommitMode, Consumer, StreamConsumer},
+    message::{BorrowedMessage, Headers, OwnedMessage},
+    producer::{FutureProducer, FutureRecord},
+    Message, Offset, TopicPartitionList,
 };
 use std::str;
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::{sync::mpsc, time::Instant};
 use tracing::{debug, error, warn};
 
-use crate::otel;
+use crate::{
+    batch::{BatchRange, DEFAULT_BATCH_SIZE, DEFAULT_BATCH_TIMEOUT},
+    concurrency::{
+        spawn_worker, Completed, Concurrency, OffsetTracker, Queued, DEFAULT_MAX_IN_FLIGHT,
+    },
+    connection::{client_config, producer_config, with_properties},
+    context::{
+        DispatcherContext, RebalanceEvent, RebalanceListener, DEFAULT_STATISTICS_INTERVAL_MS,
+    },
+    errors::KafkaError,
+    otel,
+    publisher::KafkaPublisher,
+    replay::{resolve_offset, ReplayProgress, ReplaySummary, SeekPosition, DEFAULT_LOOKUP_TIMEOUT},
+    retry::{
+        failure_route, republish_headers, retry_not_before, FailureBackoff, FailureRoute,
+        ERROR_HEADER_KEY, RETRY_ATTEMPT_HEADER_KEY, RETRY_NOT_BEFORE_HEADER_KEY,
+    },
+    routing::MsgTypeStrategy,
+    schema_registry::{SchemaSerializer, SCHEMA_ID_HEADER_KEY},
+    topic::TopicDefinition,
+};
+
+///Time given to the workers to finish the queued records on shutdown
+const SHUTDOWN_DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
 
 pub struct KafkaDispatcher {
-    consumer: Arc<StreamConsumer>,
+    consumer: Arc<StreamConsumer<DispatcherContext>>,
+    producer: Arc<FutureProducer>,
+    topics: Vec<String>,
+    topics_def: Vec<TopicDefinition>,
     dispatchers: HashMap<String, Arc<dyn ConsumerHandler>>,
+    batch_dispatchers: HashMap<String, Arc<dyn BatchConsumerHandler>>,
+    batch_size: usize,
+    batch_timeout: Duration,
+    transaction: Option<Arc<KafkaPublisher>>,
+    concurrency: Concurrency,
+    max_in_flight: usize,
+    serializer: Option<Arc<SchemaSerializer>>,
+    msg_type_strategy: MsgTypeStrategy,
+    start_positions: HashMap<(String, i32), SeekPosition>,
+    start_all: Option<SeekPosition>,
+    started: Mutex<HashSet<(String, i32)>>,
+}
+
+/// Handler lookup and failure routing, shared by the dispatcher and its workers
+#[derive(Clone)]
+pub(crate) struct RecordProcessor {
+    producer: Arc<FutureProducer>,
+    topics_def: Arc<Vec<TopicDefinition>>,
+    dispatchers: Arc<HashMap<String, Arc<dyn ConsumerHandler>>>,
+    batch_dispatchers: Arc<HashMap<String, Arc<dyn BatchConsumerHandler>>>,
+    serializer: Option<Arc<SchemaSerializer>>,
+    msg_type_strategy: MsgTypeStrategy,
+}
+
+/// Partition paused until its retry record is due, or until the failure backoff elapsed
+struct DelayedPartition {
+    topic: String,
+    partition: i32,
+    offset: i64,
+    not_before: i64,
 }
 
 impl KafkaDispatcher {
-    pub fn new<T>(cfgs: &Configs<T>) -> Result<Arc<Self>, MessagingError>
+    pub fn new<T>(cfgs: &Configs<T>) -> Result<Self, MessagingError>
     where
         T: DynamicConfigs,
     {
-        let log_level = match cfgs.app.env {
-            Environment::Local | Environment::Dev => rdkafka::config::RDKafkaLogLevel::Debug,
-            Environment::Staging | Environment::Prod => rdkafka::config::RDKafkaLogLevel::Info,
+        let group_id = match cfgs.kafka.group_id.is_empty() {
+            true => cfgs.app.name.clone(),
+            false => cfgs.kafka.group_id.clone(),
         };
 
-        let consumer = match ClientConfig::new()
-            .set(
-                "bootstrap.servers",
-                format!("{}:{}", cfgs.kafka.host, cfgs.kafka.port),
-            )
-            .set("client.id", cfgs.app.name.clone())
+        let mut config = client_config(cfgs);
+        config
+            .set("group.id", group_id)
+            .set("auto.offset.reset", cfgs.kafka.auto_offset_reset.clone())
+            // offsets are committed by the dispatcher once the handler succeeds
+            .set("enable.auto.commit", "false")
+            .set("enable.auto.offset.store", "false")
             .set("message.timeout.ms", cfgs.kafka.timeout.to_string())
-            .set("security.protocol", cfgs.kafka.security_protocol.clone()) //security.protocol=SASL_PLAINTEXT or SASL_SSL
-            .set("sasl.mechanism", cfgs.kafka.sasl_mechanisms.clone()) //sasl.mechanism=PLAIN
-            .set("sasl.username", cfgs.kafka.user.clone())
-            .set("sasl.password", cfgs.kafka.password.clone())
-            .set_log_level(log_level)
-            .create::<StreamConsumer>()
+            .set(
+                "statistics.interval.ms",
+                DEFAULT_STATISTICS_INTERVAL_MS.to_string(),
+            );
+
+        let consumer = match with_properties(&mut config, cfgs)
+            .create_with_context::<_, StreamConsumer<DispatcherContext>>(DispatcherContext::new())
+        {
+            Ok(p) => Ok(p),
+            Err(err) => {
+                error!(error = err.to_string(), "failure to create kafka consumer");
+                Err(MessagingError::ConnectionError {})
+            }
+        }?;
+
+        // republishes failed records to the retry and dead-letter topics
+        let producer = match with_properties(producer_config(cfgs).set("acks", "all"), cfgs)
+            .create::<FutureProducer>()
         {
             Ok(p) => Ok(p),
             Err(err) => {
@@ -56,35 +132,271 @@ impl KafkaDispatcher {
             }
         }?;
 
-        Ok(Arc::new(Self {
+        Ok(Self {
             consumer: Arc::new(consumer),
+            producer: Arc::new(producer),
+            topics: vec![],
+            topics_def: vec![],
             dispatchers: HashMap::new(),
-        }))
+            batch_dispatchers: HashMap::new(),
+            batch_size: DEFAULT_BATCH_SIZE,
+            batch_timeout: DEFAULT_BATCH_TIMEOUT,
+            transaction: None,
+            concurrency: Concurrency::Sequential,
+            max_in_flight: DEFAULT_MAX_IN_FLIGHT,
+            serializer: None,
+            msg_type_strategy: MsgTypeStrategy::Header,
+            start_positions: HashMap::new(),
+            start_all: None,
+            started: Mutex::new(HashSet::new()),
+        })
+    }
+
+    /// Each record is handled inside a transaction of `publisher`, created with
+    /// `KafkaPublisher::new_transactional`. Messages published by the handlers through
+    /// the same publisher are committed atomically with the consumed offset.
+    pub fn transactional(mut self, publisher: Arc<KafkaPublisher>) -> Self {
+        self.transaction = Some(publisher);
+        self
+    }
+
+    /// Records of different partitions (or keys) are handled in parallel by worker tasks,
+    /// not supported together with `transactional`.
+    pub fn concurrency(mut self, concurrency: Concurrency) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+
+    /// Records of a partition being handled before it is paused, used by the concurrent modes
+    pub fn max_in_flight(mut self, max: usize) -> Self {
+        self.max_in_flight = max.max(1);
+        self
+    }
+
+    /// Records of `definition` are delivered in batches of up to `batch_size` records or
+    /// whatever arrived within `batch_timeout`. The offsets are committed once the whole
+    /// batch is handled, so the dispatcher only supports sequential processing.
+    /// A handler returning `Err` rewinds the batch, which is consumed again after a capped
+    /// backoff without going through the retry/dead-letter topics; return the failed
+    /// records instead for them to follow the retry flow.
+    pub fn register_batch(
+        mut self,
+        definition: &DispatcherDefinition,
+        handler: Arc<dyn BatchConsumerHandler>,
+    ) -> Self {
+        if !self.topics.contains(&definition.name) {
+            self.topics.push(definition.name.clone());
+        }
+
+        self.batch_dispatchers
+            .insert(definition.msg_type.clone(), handler);
+
+        self
+    }
+
+    pub fn batch_size(mut self, size: usize) -> Self {
+        self.batch_size = size.max(1);
+        self
+    }
+
+    pub fn batch_timeout(mut self, timeout: Duration) -> Self {
+        self.batch_timeout = timeout;
+        self
+    }
+
+    /// Notified when partitions are assigned to or revoked from this consumer, see
+    /// `RebalanceListener` for the offsets of the revoked partitions
+    pub fn rebalance_listener(self, listener: Arc<dyn RebalanceListener>) -> Self {
+        self.consumer.context().set_listener(listener);
+        self
+    }
+
+    /// Payloads are read in the Schema Registry wire format, handlers receive the encoded
+    /// record without the framing and its schema id in the `kafka-schema-id` header.
+    pub fn schema_registry(mut self, serializer: Arc<SchemaSerializer>) -> Self {
+        self.serializer = Some(serializer);
+        self
+    }
+
+    /// Where the `msg_type` of the records is read. Default: `MsgTypeStrategy::Header`
+    pub fn msg_type_strategy(mut self, strategy: MsgTypeStrategy) -> Self {
+        self.msg_type_strategy = strategy;
+        self
+    }
+
+    /// Partition consumed from `position` the first time it is assigned to this consumer,
+    /// instead of the committed offset.
+    pub fn start_from(mut self, topic: &str, partition: i32, position: SeekPosition) -> Self {
+        self.start_positions
+            .insert((topic.to_owned(), partition), position);
+        self
+    }
+
+    /// Every partition of the registered topics without a `start_from` position is
+    /// consumed from `position`, e.g. `SeekPosition::Timestamp` to reprocess an incident.
+    pub fn start_all_from(mut self, position: SeekPosition) -> Self {
+        self.start_all = Some(position);
+        self
+    }
+
+    /// Retry and dead-letter settings of the registered topics, topics without
+    /// a definition only log handler failures.
+    pub fn topics_def(mut self, defs: Vec<TopicDefinition>) -> Self {
+        self.topics_def = defs;
+        self
     }
 }
 
 #[async_trait]
 impl Dispatcher for KafkaDispatcher {
+    /// `definition.name` is the topic subscribed by the dispatcher.
     fn register(
         mut self,
         definition: &DispatcherDefinition,
         handler: Arc<dyn ConsumerHandler>,
     ) -> Self {
+        if !self.topics.contains(&definition.name) {
+            self.topics.push(definition.name.clone());
+        }
+
         self.dispatchers
             .insert(definition.msg_type.clone(), handler);
 
         self
     }
 
+    /// Consumes until SIGINT/SIGTERM, then commits the processed offsets and unsubscribes.
     async fn consume_blocking(&self) -> Result<(), MessagingError> {
-        tokio::spawn({
-            let consumer = self.consumer.clone();
-            let dispatchers = self.dispatchers.clone();
-            let tracer = global::tracer("kafka-consume-blocking");
-
-            async move {
-                loop {
-                    let received = match consumer.recv().await {
+        if self.transaction.is_some() && self.concurrency != Concurrency::Sequential {
+            error!("transactional dispatcher only supports sequential processing");
+            return Err(MessagingError::CreatingConsumerError);
+        }
+
+        let batched = !self.batch_dispatchers.is_empty();
+        if batched && (self.transaction.is_some() || self.concurrency != Concurrency::Sequential) {
+            error!("batch dispatcher only supports sequential and non-transactional processing");
+            return Err(MessagingError::CreatingConsumerError);
+        }
+
+        let mut subscriptions = vec![];
+        for topic in &self.topics {
+            match self.topic_def(topic) {
+                Some(def) => subscriptions.extend(def.topics()),
+                None => subscriptions.push(topic.clone()),
+            }
+        }
+
+        let topics: Vec<&str> = subscriptions.iter().map(|t| t.as_str()).collect();
+
+        match self.consumer.subscribe(&topics) {
+            Err(err) => {
+                error!(
+                    error = err.to_string(),
+                    "failure to subscribe to the topics"
+                );
+                Err(MessagingError::CreatingConsumerError)
+            }
+            _ => Ok(()),
+        }?;
+
+        debug!(topics = topics.join(","), "subscribed");
+
+        let processor = self.processor();
+
+        match self.concurrency {
+            Concurrency::Sequential if batched => self.consume_batched(&processor).await,
+            Concurrency::Sequential => self.consume_sequential(&processor).await,
+            _ => self.consume_concurrent(&processor).await,
+        }
+
+        self.consumer.unsubscribe();
+
+        Ok(())
+    }
+}
+
+impl KafkaDispatcher {
+    /// Consumes the records of the registered topics from `from` until `to` (exclusive)
+    /// and stops, without joining the consumer group or committing offsets. Failed records
+    /// are only counted, a SIGINT/SIGTERM ends the replay early.
+    pub async fn replay(
+        &self,
+        from: SeekPosition,
+        to: SeekPosition,
+    ) -> Result<ReplaySummary, KafkaError> {
+        let mut ranges = vec![];
+
+        for topic in &self.topics {
+            let partitions: Vec<i32> = match self
+                .consumer
+                .fetch_metadata(Some(topic), DEFAULT_LOOKUP_TIMEOUT)
+            {
+                Err(err) => {
+                    error!(
+                        error = err.to_string(),
+                        topic = topic,
+                        "failure to fetch metadata"
+                    );
+                    Err(KafkaError::MetadataError)
+                }
+                Ok(metadata) => Ok(metadata
+                    .topics()
+                    .iter()
+                    .flat_map(|t| t.partitions().iter().map(|p| p.id()))
+                    .collect()),
+            }?;
+
+            for partition in partitions {
+                let start = resolve_offset(self.consumer.as_ref(), topic, partition, from)?;
+                let end = resolve_offset(self.consumer.as_ref(), topic, partition, to)?;
+                ranges.push((topic.clone(), partition, start, end));
+            }
+        }
+
+        let mut progress = ReplayProgress::new(ranges);
+        let mut summary = ReplaySummary::default();
+
+        if progress.is_done() {
+            debug!("nothing to replay");
+            return Ok(summary);
+        }
+
+        match self.consumer.assign(&progress.assignment()) {
+            Err(err) => {
+                error!(
+                    error = err.to_string(),
+                    "failure to assign replay partitions"
+                );
+                Err(KafkaError::AssignError)
+            }
+            _ => Ok(()),
+        }?;
+
+        let processor = self.processor();
+        let tracer = global::tracer("kafka-replay");
+
+        let shutdown = shutdown_signal();
+        tokio::pin!(shutdown);
+
+        let mut tick = tokio::time::interval(Duration::from_secs(1));
+
+        while !progress.is_done() {
+            tokio::select! {
+                _ = &mut shutdown => {
+                    warn!("shutdown signal received, stopping the replay");
+                    break;
+                }
+                _ = tick.tick() => {
+                    if let Ok(positions) = self.consumer.position() {
+                        for elem in positions.elements() {
+                            if let Offset::Offset(position) = elem.offset() {
+                                progress.reached(elem.topic(), elem.partition(), position);
+                            }
+                        }
+                    }
+                }
+                received = self.consumer.recv() => {
+                    let received = match received {
                         Ok(m) => m,
                         Err(err) => {
                             error!(error = err.to_string(), "failure to consume message");
@@ -92,88 +404,1070 @@ impl Dispatcher for KafkaDispatcher {
                         }
                     };
 
-                    let topic = received.topic();
+                    if !progress.accept(received.topic(), received.partition(), received.offset()) {
+                        continue;
+                    }
 
-                    debug!("topic: {} - received message", topic);
+                    summary.records += 1;
+                    if processor.dispatch(&tracer, &received).await.is_err() {
+                        summary.failures += 1;
+                    }
+                }
+            }
+        }
 
-                    let msg_type = match received.key() {
-                        Some(k) => match str::from_utf8(k) {
-                            Ok(tpy) => tpy,
-                            Err(err) => {
-                                error!(
-                                    error = err.to_string(),
-                                    topic = topic,
-                                    "key conversion to utf8 error"
-                                );
-                                continue;
-                            }
-                        },
-                        _ => {
-                            error!(
-                                topic = topic,
-                                "ignoring message - message with no key (msg_type)"
-                            );
+        if let Err(err) = self.consumer.unassign() {
+            warn!(
+                error = err.to_string(),
+                "failure to unassign replay partitions"
+            );
+        }
+
+        debug!(
+            records = summary.records,
+            failures = summary.failures,
+            "replay finished"
+        );
+
+        Ok(summary)
+    }
+
+    fn processor(&self) -> RecordProcessor {
+        RecordProcessor {
+            producer: self.producer.clone(),
+            topics_def: Arc::new(self.topics_def.clone()),
+            dispatchers: Arc::new(self.dispatchers.clone()),
+            batch_dispatchers: Arc::new(self.batch_dispatchers.clone()),
+            serializer: self.serializer.clone(),
+            msg_type_strategy: self.msg_type_strategy.clone(),
+        }
+    }
+
+    async fn consume_sequential(&self, processor: &RecordProcessor) {
+        let tracer = global::tracer("kafka-consume-blocking");
+
+        let shutdown = shutdown_signal();
+        tokio::pin!(shutdown);
+
+        let mut delayed: Vec<DelayedPartition> = vec![];
+        let mut backoff = FailureBackoff::default();
+
+        loop {
+            let next_due = delayed.iter().map(|d| d.not_before).min();
+            let wait = Duration::from_millis(next_due.map_or(0, |due| (due - now()).max(0) as u64));
+
+            tokio::select! {
+                _ = &mut shutdown => {
+                    debug!("shutdown signal received, stopping the consumer");
+                    break;
+                }
+                _ = tokio::time::sleep(wait), if next_due.is_some() => {
+                    self.rebalanced(|topic, partition| {
+                        delayed.retain(|d| d.topic != topic || d.partition != partition);
+                        backoff.forget(topic, partition);
+                    });
+                    self.resume_due(&mut delayed);
+                }
+                received = self.consumer.recv() => {
+                    let moved = self.rebalanced(|topic, partition| {
+                        delayed.retain(|d| d.topic != topic || d.partition != partition);
+                        backoff.forget(topic, partition);
+                    });
+
+                    let received = match received {
+                        Ok(m) => m,
+                        Err(err) => {
+                            error!(error = err.to_string(), "failure to consume message");
                             continue;
                         }
                     };
 
-                    if received.payload().is_none() {
-                        warn!(
-                            topic = topic,
-                            msg_type = msg_type,
-                            "ignoring msg - message with no payload"
-                        );
+                    // fetched before the partition was moved to its start position
+                    if moved.contains(&(received.topic().to_owned(), received.partition())) {
+                        continue;
+                    }
+
+                    // fetched before the partition was paused, it is consumed again once resumed
+                    if is_delayed(&delayed, received.topic(), received.partition()) {
                         continue;
                     }
 
-                    let handler = match dispatchers.get(msg_type) {
-                        Some(h) => h,
-                        _ => {
-                            warn!(
-                                topic = topic,
-                                msg_type = msg_type,
-                                "ignoring message - there is no handler registered for this msg_type",
-                            );
+                    if let Some(not_before) = retry_not_before(&received) {
+                        if not_before > now() {
+                            self.delay(&received, not_before, &mut delayed);
+                            continue;
+                        }
+                    }
+
+                    let processed = match &self.transaction {
+                        Some(publisher) => self.process_transactional(processor, publisher, &tracer, &received).await,
+                        None => self.process(processor, &tracer, &received).await,
+                    };
 
+                    match processed {
+                        Ok(()) => backoff.forget(received.topic(), received.partition()),
+                        Err(_) => {
+                            let wait = backoff.failed(received.topic(), received.partition());
+                            self.delay(&received, now() + wait.as_millis() as i64, &mut delayed);
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Err(err) = self.consumer.commit_consumer_state(CommitMode::Sync) {
+            warn!(
+                error = err.to_string(),
+                "failure to commit offsets on shutdown"
+            );
+        }
+    }
+
+    /// Collects the records until the batch is full or its timeout elapses, then hands it
+    /// to `RecordProcessor::dispatch_batch`. The pending batch is handled on shutdown.
+    async fn consume_batched(&self, processor: &RecordProcessor) {
+        let tracer = global::tracer("kafka-consume-batch");
+
+        let shutdown = shutdown_signal();
+        tokio::pin!(shutdown);
+
+        let mut delayed: Vec<DelayedPartition> = vec![];
+        let mut backoff = FailureBackoff::default();
+        let mut batch: Vec<OwnedMessage> = vec![];
+        let mut deadline: Option<Instant> = None;
+
+        loop {
+            let next_due = delayed.iter().map(|d| d.not_before).min();
+            let wait = Duration::from_millis(next_due.map_or(0, |due| (due - now()).max(0) as u64));
+            let batch_wait = deadline.map_or(Duration::ZERO, |d| {
+                d.saturating_duration_since(Instant::now())
+            });
+
+            tokio::select! {
+                _ = &mut shutdown => {
+                    debug!("shutdown signal received, stopping the consumer");
+                    break;
+                }
+                _ = tokio::time::sleep(wait), if next_due.is_some() => {
+                    self.rebalanced(|topic, partition| {
+                        delayed.retain(|d| d.topic != topic || d.partition != partition);
+                        batch.retain(|r| r.topic() != topic || r.partition() != partition);
+                        backoff.forget(topic, partition);
+                    });
+                    self.resume_due(&mut delayed);
+                }
+                _ = tokio::time::sleep(batch_wait), if deadline.is_some() => {
+                    deadline = None;
+                    let batch = std::mem::take(&mut batch);
+                    self.process_batch(processor, &tracer, batch, CommitMode::Async, &mut delayed, &mut backoff).await;
+                }
+                received = self.consumer.recv() => {
+                    let moved = self.rebalanced(|topic, partition| {
+                        delayed.retain(|d| d.topic != topic || d.partition != partition);
+                        batch.retain(|r| r.topic() != topic || r.partition() != partition);
+                        backoff.forget(topic, partition);
+                    });
+
+                    let received = match received {
+                        Ok(m) => m,
+                        Err(err) => {
+                            error!(error = err.to_string(), "failure to consume message");
                             continue;
                         }
                     };
 
-                    let (ctx, headers) = explode(topic, msg_type, &tracer, received.headers());
-                    let consumer_msg =
-                        ConsumerMessage::new(topic, msg_type, received.payload().unwrap(), headers);
+                    // fetched before the partition was moved to its start position
+                    if moved.contains(&(received.topic().to_owned(), received.partition())) {
+                        continue;
+                    }
+
+                    // fetched before the partition was paused, it is consumed again once resumed
+                    if is_delayed(&delayed, received.topic(), received.partition()) {
+                        continue;
+                    }
+
+                    if let Some(not_before) = retry_not_before(&received) {
+                        if not_before > now() {
+                            self.delay(&received, not_before, &mut delayed);
+                            continue;
+                        }
+                    }
+
+                    batch.push(received.detach());
+                    deadline.get_or_insert_with(|| Instant::now() + self.batch_timeout);
+
+                    if batch.len() >= self.batch_size {
+                        deadline = None;
+                        let batch = std::mem::take(&mut batch);
+                        self.process_batch(processor, &tracer, batch, CommitMode::Async, &mut delayed, &mut backoff).await;
+                    }
+                }
+            }
+        }
+
+        self.process_batch(
+            processor,
+            &tracer,
+            batch,
+            CommitMode::Sync,
+            &mut delayed,
+            &mut backoff,
+        )
+        .await;
+    }
+
+    /// Commits the batch once handled, otherwise pauses its partitions so the whole
+    /// batch is consumed again once the failure backoff elapsed.
+    async fn process_batch(
+        &self,
+        processor: &RecordProcessor,
+        tracer: &BoxedTracer,
+        batch: Vec<OwnedMessage>,
+        mode: CommitMode,
+        delayed: &mut Vec<DelayedPartition>,
+        backoff: &mut FailureBackoff,
+    ) {
+        if batch.is_empty() {
+            return;
+        }
+
+        let range = BatchRange::new(&batch);
+
+        if processor.dispatch_batch(tracer, &batch).await.is_ok() {
+            for (topic, partition, _) in range.rewind_positions() {
+                backoff.forget(topic, partition);
+            }
+
+            if let Err(err) = self.consumer.commit(&range.commit_positions(), mode) {
+                error!(error = err.to_string(), "failure to commit batch offsets");
+            }
+            return;
+        }
+
+        for (topic, partition, offset) in range.rewind_positions() {
+            let wait = backoff.failed(topic, partition);
+            self.delay_at(
+                topic,
+                partition,
+                offset,
+                now() + wait.as_millis() as i64,
+                delayed,
+            );
+        }
+    }
+
+    /// Fans the records out to the workers, pausing the partitions with `max_in_flight`
+    /// records being handled. On shutdown the queued records are drained before the
+    /// final commit.
+    async fn consume_concurrent(&self, processor: &RecordProcessor) {
+        let shutdown = shutdown_signal();
+        tokio::pin!(shutdown);
 
-                    match handler.exec(&ctx, &consumer_msg).await {
+        let (done_tx, mut done_rx) = mpsc::unbounded_channel::<Completed>();
+        let mut workers = HashMap::new();
+        let mut tracker = OffsetTracker::default();
+        let mut paused = HashSet::new();
+        let mut delayed: Vec<DelayedPartition> = vec![];
+        let mut backoff = FailureBackoff::default();
+
+        loop {
+            let next_due = delayed.iter().map(|d| d.not_before).min();
+            let wait = Duration::from_millis(next_due.map_or(0, |due| (due - now()).max(0) as u64));
+
+            tokio::select! {
+                _ = &mut shutdown => {
+                    debug!("shutdown signal received, stopping the consumer");
+                    break;
+                }
+                _ = tokio::time::sleep(wait), if next_due.is_some() => {
+                    self.rebalanced(|topic, partition| {
+                        tracker.forget(topic, partition);
+                        paused.remove(&(topic.to_owned(), partition));
+                        delayed.retain(|d| d.topic != topic || d.partition != partition);
+                        backoff.forget(topic, partition);
+                    });
+                    for key in self.resume_due(&mut delayed) {
+                        paused.remove(&key);
+                    }
+                }
+                Some(done) = done_rx.recv() => {
+                    self.rebalanced(|topic, partition| {
+                        tracker.forget(topic, partition);
+                        paused.remove(&(topic.to_owned(), partition));
+                        delayed.retain(|d| d.topic != topic || d.partition != partition);
+                        backoff.forget(topic, partition);
+                    });
+                    self.completed(&mut tracker, &mut paused, &mut delayed, &mut backoff, done);
+                }
+                received = self.consumer.recv() => {
+                    let moved = self.rebalanced(|topic, partition| {
+                        tracker.forget(topic, partition);
+                        paused.remove(&(topic.to_owned(), partition));
+                        delayed.retain(|d| d.topic != topic || d.partition != partition);
+                        backoff.forget(topic, partition);
+                    });
+
+                    let received = match received {
+                        Ok(m) => m,
                         Err(err) => {
-                            error!(
-                                error = err.to_string(),
-                                topic = topic,
-                                msg_type = msg_type,
-                                "error whiling processing message"
-                            );
+                            error!(error = err.to_string(), "failure to consume message");
                             continue;
                         }
-                        _ => {
-                            debug!(
-                                topic = topic,
-                                msg_type = msg_type,
-                                "message processed succeffly"
-                            )
+                    };
+
+                    // fetched before the partition was moved to its start position
+                    if moved.contains(&(received.topic().to_owned(), received.partition())) {
+                        continue;
+                    }
+
+                    // fetched before the partition was paused, it is consumed again once resumed
+                    if is_delayed(&delayed, received.topic(), received.partition()) {
+                        continue;
+                    }
+
+                    if let Some(not_before) = retry_not_before(&received) {
+                        if not_before > now() {
+                            self.delay(&received, not_before, &mut delayed);
+                            continue;
                         }
+                    }
+
+                    let topic = received.topic().to_owned();
+                    let partition = received.partition();
+
+                    if tracker.handled(&topic, partition, received.offset()) {
+                        let done = Completed {
+                            topic,
+                            partition,
+                            offset: received.offset(),
+                            failed: false,
+                        };
+                        self.completed(&mut tracker, &mut paused, &mut delayed, &mut backoff, done);
+                        continue;
+                    }
+
+                    tracker.track(&topic, partition, received.offset());
+
+                    let worker = workers
+                        .entry(self.concurrency.worker_id(&received))
+                        .or_insert_with(|| spawn_worker(processor.clone(), self.concurrency, done_tx.clone()));
+
+                    let queued = Queued {
+                        generation: tracker.generation(&topic, partition),
+                        received: received.detach(),
                     };
+                    if worker.send(queued).is_err() {
+                        error!(topic = topic, partition = partition, "worker stopped");
+                    }
+
+                    if tracker.in_flight(&topic, partition) >= self.max_in_flight {
+                        self.pause(&mut paused, topic, partition);
+                    }
+                }
+            }
+        }
+
+        drop(workers);
+        drop(done_tx);
+
+        let drain = async {
+            while let Some(done) = done_rx.recv().await {
+                self.completed(&mut tracker, &mut paused, &mut delayed, &mut backoff, done);
+            }
+        };
+
+        if tokio::time::timeout(SHUTDOWN_DRAIN_TIMEOUT, drain)
+            .await
+            .is_err()
+        {
+            warn!("timeout waiting the workers, in-flight records will be consumed again");
+        }
+
+        if let Err(err) = self.consumer.commit(&tracker.positions(), CommitMode::Sync) {
+            warn!(
+                error = err.to_string(),
+                "failure to commit offsets on shutdown"
+            );
+        }
+    }
+
+    /// Drops the local state of the revoked partitions, records of these partitions still
+    /// being handled are not committed and will be consumed again by their new owner.
+    /// Partitions assigned for the first time are moved to their start position, which
+    /// are returned.
+    fn rebalanced<F>(&self, mut forget: F) -> HashSet<(String, i32)>
+    where
+        F: FnMut(&str, i32),
+    {
+        let mut moved = HashSet::new();
+
+        for event in self.consumer.context().take_events() {
+            match event {
+                RebalanceEvent::Revoked(partitions) => {
+                    for (topic, partition) in partitions {
+                        moved.remove(&(topic.clone(), partition));
+                        forget(&topic, partition);
+                    }
+                }
+                RebalanceEvent::Assigned(partitions) => {
+                    for (topic, partition) in partitions {
+                        if self.seek_start(&topic, partition) {
+                            moved.insert((topic, partition));
+                        }
+                    }
                 }
             }
+        }
+
+        moved
+    }
+
+    /// Moves a partition to its start position the first time it is assigned
+    fn seek_start(&self, topic: &str, partition: i32) -> bool {
+        let position = match self.start_positions.get(&(topic.to_owned(), partition)) {
+            Some(position) => *position,
+            None if self.topics.iter().any(|t| t == topic) => match self.start_all {
+                Some(position) => position,
+                None => return false,
+            },
+            None => return false,
+        };
+
+        let first_assignment = match self.started.lock() {
+            Ok(mut started) => started.insert((topic.to_owned(), partition)),
+            Err(_) => false,
+        };
+        if !first_assignment {
+            return false;
+        }
+
+        let Ok(offset) = resolve_offset(self.consumer.as_ref(), topic, partition, position) else {
+            return false;
+        };
+
+        match self.consumer.seek(
+            topic,
+            partition,
+            Offset::Offset(offset),
+            DEFAULT_LOOKUP_TIMEOUT,
+        ) {
+            Err(err) => {
+                error!(
+                    error = err.to_string(),
+                    topic = topic,
+                    partition = partition,
+                    "failure to move partition to its start position"
+                );
+                false
+            }
+            _ => {
+                debug!(
+                    topic = topic,
+                    partition = partition,
+                    offset = offset,
+                    "partition moved to its start position"
+                );
+                true
+            }
+        }
+    }
+
+    /// A failed record pauses its partition until the in-flight records are handled, the
+    /// partition then starts over from it once the failure backoff elapsed.
+    fn completed(
+        &self,
+        tracker: &mut OffsetTracker,
+        paused: &mut HashSet<(String, i32)>,
+        delayed: &mut Vec<DelayedPartition>,
+        backoff: &mut FailureBackoff,
+        done: Completed,
+    ) {
+        if done.failed {
+            tracker.fail(&done.topic, done.partition, done.offset);
+            self.pause(paused, done.topic.clone(), done.partition);
+        } else if let Some(position) = tracker.complete(&done.topic, done.partition, done.offset) {
+            backoff.forget(&done.topic, done.partition);
+
+            let mut tpl = TopicPartitionList::new();
+            let _ = tpl.add_partition_offset(&done.topic, done.partition, Offset::Offset(position));
+
+            if let Err(err) = self.consumer.commit(&tpl, CommitMode::Async) {
+                error!(
+                    error = err.to_string(),
+                    topic = done.topic,
+                    "failure to commit offset"
+                );
+            }
+        }
+
+        if let Some(offset) = tracker.rewind(&done.topic, done.partition) {
+            let wait = backoff.failed(&done.topic, done.partition);
+
+            // a pending retry record is fetched again after the failed one
+            delayed.retain(|d| d.topic != done.topic || d.partition != done.partition);
+            delayed.push(DelayedPartition {
+                topic: done.topic.clone(),
+                partition: done.partition,
+                offset,
+                not_before: now() + wait.as_millis() as i64,
+            });
+        }
+
+        let key = (done.topic, done.partition);
+        if !tracker.has_failed(&key.0, key.1)
+            && !is_delayed(delayed, &key.0, key.1)
+            && tracker.in_flight(&key.0, key.1) < self.max_in_flight
+            && paused.remove(&key)
+        {
+            let mut tpl = TopicPartitionList::new();
+            tpl.add_partition(&key.0, key.1);
+
+            if let Err(err) = self.consumer.resume(&tpl) {
+                error!(
+                    error = err.to_string(),
+                    topic = key.0,
+                    "failure to resume partition"
+                );
+            }
+        }
+    }
+
+    fn pause(&self, paused: &mut HashSet<(String, i32)>, topic: String, partition: i32) {
+        let mut tpl = TopicPartitionList::new();
+        tpl.add_partition(&topic, partition);
+
+        if !paused.insert((topic, partition)) {
+            return;
+        }
+
+        if let Err(err) = self.consumer.pause(&tpl) {
+            error!(error = err.to_string(), "failure to pause partition");
+        }
+    }
+
+    /// Returns `Err` when the record could not be handled nor republished, the caller
+    /// has to consume it again.
+    async fn process(
+        &self,
+        processor: &RecordProcessor,
+        tracer: &BoxedTracer,
+        received: &BorrowedMessage<'_>,
+    ) -> Result<(), MessagingError> {
+        if let Err(err) = processor.dispatch(tracer, received).await {
+            processor.handle_failure(received, &err).await?;
+        }
+
+        self.commit(received);
+        Ok(())
+    }
+
+    /// The handler output and the consumed offset are committed in the same transaction.
+    /// When the handler fails the transaction is aborted and the record follows the
+    /// retry/dead-letter flow outside of it; when the transaction itself fails the
+    /// caller has to consume the record again.
+    async fn process_transactional(
+        &self,
+        processor: &RecordProcessor,
+        publisher: &KafkaPublisher,
+        tracer: &BoxedTracer,
+        received: &BorrowedMessage<'_>,
+    ) -> Result<(), MessagingError> {
+        publisher.begin_transaction()?;
+
+        if let Err(err) = processor.dispatch(tracer, received).await {
+            publisher.abort_transaction()?;
+            processor.handle_failure(received, &err).await?;
+
+            self.commit(received);
+            return Ok(());
+        }
+
+        if let Err(err) = self.commit_transaction(publisher, received) {
+            let _ = publisher.abort_transaction();
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    fn commit_transaction(
+        &self,
+        publisher: &KafkaPublisher,
+        received: &BorrowedMessage<'_>,
+    ) -> Result<(), MessagingError> {
+        let Some(cgm) = self.consumer.group_metadata() else {
+            error!("consumer group metadata unavailable for the transaction");
+            return Err(MessagingError::TransactionError(
+                "consumer group metadata".to_owned(),
+            ));
+        };
+
+        let mut offsets = TopicPartitionList::new();
+        if let Err(err) = offsets.add_partition_offset(
+            received.topic(),
+            received.partition(),
+            Offset::Offset(received.offset() + 1),
+        ) {
+            error!(error = err.to_string(), "invalid transaction offset");
+            return Err(MessagingError::TransactionError(err.to_string()));
+        }
+
+        publisher.send_offsets_to_transaction(&offsets, &cgm)?;
+        publisher.commit_transaction()
+    }
+
+    fn commit(&self, received: &BorrowedMessage<'_>) {
+        if let Err(err) = self.consumer.commit_message(received, CommitMode::Async) {
+            error!(
+                error = err.to_string(),
+                topic = received.topic(),
+                "failure to commit offset"
+            );
+        }
+    }
+
+    fn topic_def(&self, topic: &str) -> Option<&TopicDefinition> {
+        self.topics_def.iter().find(|def| def.name == topic)
+    }
+
+    /// Pauses the partition and rewinds it to the record, so it is fetched again once due
+    fn delay(
+        &self,
+        received: &BorrowedMessage<'_>,
+        not_before: i64,
+        delayed: &mut Vec<DelayedPartition>,
+    ) {
+        self.delay_at(
+            received.topic(),
+            received.partition(),
+            received.offset(),
+            not_before,
+            delayed,
+        );
+    }
+
+    fn delay_at(
+        &self,
+        topic: &str,
+        partition: i32,
+        offset: i64,
+        not_before: i64,
+        delayed: &mut Vec<DelayedPartition>,
+    ) {
+        let mut tpl = TopicPartitionList::new();
+        tpl.add_partition(topic, partition);
+
+        if let Err(err) = self.consumer.pause(&tpl) {
+            error!(
+                error = err.to_string(),
+                topic = topic,
+                "failure to pause partition"
+            );
+        }
+
+        debug!(
+            topic = topic,
+            partition = partition,
+            not_before = not_before,
+            "record is not due, pausing partition"
+        );
+
+        delayed.push(DelayedPartition {
+            topic: topic.to_owned(),
+            partition,
+            offset,
+            not_before,
         });
+    }
+
+    /// Rewinds and resumes the partitions that are due, which are returned
+    fn resume_due(&self, delayed: &mut Vec<DelayedPartition>) -> Vec<(String, i32)> {
+        let now = now();
+        let mut resumed = vec![];
+
+        delayed.retain(|d| {
+            if d.not_before > now {
+                return true;
+            }
+
+            resumed.push((d.topic.clone(), d.partition));
+
+            if let Err(err) = self.consumer.seek(
+                &d.topic,
+                d.partition,
+                Offset::Offset(d.offset),
+                Duration::from_secs(5),
+            ) {
+                error!(
+                    error = err.to_string(),
+                    topic = d.topic,
+                    "failure to rewind delayed partition"
+                );
+            }
+
+            let mut tpl = TopicPartitionList::new();
+            tpl.add_partition(&d.topic, d.partition);
+
+            if let Err(err) = self.consumer.resume(&tpl) {
+                error!(
+                    error = err.to_string(),
+                    topic = d.topic,
+                    "failure to resume partition"
+                );
+            }
+
+            false
+        });
+
+        resumed
+    }
+}
+
+impl RecordProcessor {
+    /// Definition and number of previous failures of a record read from `topic`
+    fn resolve(&self, topic: &str) -> Option<(&TopicDefinition, usize)> {
+        self.topics_def
+            .iter()
+            .find_map(|def| def.attempt(topic).map(|attempt| (def, attempt)))
+    }
+
+    /// Republishes the failed record to the next retry topic or to the dead-letter topic.
+    /// On failure the caller must not commit the offset and has to rewind the partition,
+    /// so the record is consumed again.
+    pub(crate) async fn handle_failure<M: Message>(
+        &self,
+        received: &M,
+        err: &MessagingError,
+    ) -> Result<(), MessagingError> {
+        let Some((def, attempt)) = self.resolve(received.topic()) else {
+            return Ok(());
+        };
+
+        let (target, headers) = match failure_route(def, attempt, now()) {
+            FailureRoute::Retry {
+                topic,
+                attempt,
+                not_before,
+            } => {
+                warn!(
+                    topic = def.name,
+                    attempt = attempt,
+                    "error whiling processing message, sending to {}",
+                    topic
+                );
+
+                let headers = republish_headers(
+                    received,
+                    &[
+                        (RETRY_ATTEMPT_HEADER_KEY, attempt.to_string()),
+                        (RETRY_NOT_BEFORE_HEADER_KEY, not_before.to_string()),
+                    ],
+                );
+
+                (topic, headers)
+            }
+            FailureRoute::DeadLetter { topic, attempts } => {
+                error!(
+                    topic = def.name,
+                    attempts = attempts,
+                    "too many attempts, sending to {}",
+                    topic
+                );
+
+                let headers = republish_headers(
+                    received,
+                    &[
+                        (RETRY_ATTEMPT_HEADER_KEY, attempts.to_string()),
+                        (ERROR_HEADER_KEY, err.to_string()),
+                    ],
+                );
+
+                (topic, headers)
+            }
+            FailureRoute::Discard => return Ok(()),
+        };
+
+        let mut record = FutureRecord::<[u8], [u8]>::to(&target).headers(headers);
+        if let Some(key) = received.key() {
+            record = record.key(key);
+        }
+        if let Some(payload) = received.payload() {
+            record = record.payload(payload);
+        }
+
+        match self.producer.send(record, Duration::from_secs(0)).await {
+            Err((err, _)) => {
+                error!(
+                    error = err.to_string(),
+                    topic = target,
+                    "failure to republish failed message"
+                );
+                Err(MessagingError::PublisherError)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Messages that can not be handled (no key, no payload, no handler) are skipped and
+    /// their offset committed, only handler failures return an error.
+    pub(crate) async fn dispatch<M: Message>(
+        &self,
+        tracer: &BoxedTracer,
+        received: &M,
+    ) -> Result<(), MessagingError> {
+        match self.decode(tracer, received).await? {
+            Some((ctx, msg)) => self.exec(received, &ctx, &msg).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Records with a batch handler are delivered grouped by `msg_type`, the others are
+    /// handled one by one once every batch handler succeeded. Fails when a batch handler
+    /// fails, before any record was handled on its own or republished, or when a failed
+    /// record could not be sent to the retry/dead-letter topics. Nothing is committed
+    /// on failure.
+    pub(crate) async fn dispatch_batch<M: Message>(
+        &self,
+        tracer: &BoxedTracer,
+        records: &[M],
+    ) -> Result<(), MessagingError> {
+        let mut groups: Vec<(String, Vec<usize>, Vec<ConsumerMessage>)> = vec![];
+        let mut singles: Vec<(usize, Context, ConsumerMessage)> = vec![];
+        let mut failures: Vec<(usize, MessagingError)> = vec![];
+
+        for (index, received) in records.iter().enumerate() {
+            let (ctx, msg) = match self.decode(tracer, received).await {
+                Err(err) => {
+                    failures.push((index, err));
+                    continue;
+                }
+                Ok(None) => continue,
+                Ok(Some(decoded)) => decoded,
+            };
+
+            if !self.batch_dispatchers.contains_key(&msg.msg_type) {
+                singles.push((index, ctx, msg));
+                continue;
+            }
+
+            match groups.iter_mut().find(|(t, _, _)| *t == msg.msg_type) {
+                Some((_, indexes, msgs)) => {
+                    indexes.push(index);
+                    msgs.push(msg);
+                }
+                None => groups.push((msg.msg_type.clone(), vec![index], vec![msg])),
+            }
+        }
+
+        for (msg_type, indexes, msgs) in groups {
+            let handler = &self.batch_dispatchers[&msg_type];
+            let ctx = otel::new_ctx(&msgs[0].from, &msg_type, tracer);
+
+            let batch_failures = match handler.exec(&ctx, &msgs).await {
+                Err(err) => {
+                    error!(
+                        error = err.to_string(),
+                        msg_type = msg_type,
+                        records = msgs.len(),
+                        "error whiling processing batch"
+                    );
+                    return Err(err);
+                }
+                Ok(failures) => failures,
+            };
+
+            debug!(
+                msg_type = msg_type,
+                records = msgs.len(),
+                failures = batch_failures.len(),
+                "batch processed"
+            );
+
+            for (index, err) in batch_failures {
+                let Some(record) = indexes.get(index) else {
+                    warn!(
+                        msg_type = msg_type,
+                        index = index,
+                        "ignoring batch failure - index out of the batch"
+                    );
+                    continue;
+                };
+
+                warn!(
+                    error = err.to_string(),
+                    topic = records[*record].topic(),
+                    msg_type = msg_type,
+                    "error whiling processing message"
+                );
+
+                failures.push((*record, err));
+            }
+        }
+
+        for (index, ctx, msg) in singles {
+            if let Err(err) = self.exec(&records[index], &ctx, &msg).await {
+                failures.push((index, err));
+            }
+        }
+
+        for (index, err) in failures {
+            self.handle_failure(&records[index], &err).await?;
+        }
 
         Ok(())
     }
+
+    /// Context and message of a record, `None` when the record must be skipped
+    async fn decode<M: Message>(
+        &self,
+        tracer: &BoxedTracer,
+        received: &M,
+    ) -> Result<Option<(Context, ConsumerMessage)>, MessagingError> {
+        let topic = received.topic();
+
+        debug!("topic: {} - received message", topic);
+
+        let Some(payload) = received.payload() else {
+            warn!(topic = topic, "ignoring msg - message with no payload");
+            return Ok(None);
+        };
+
+        // records from retry topics are reported as coming from the main topic
+        let from = match self.resolve(topic) {
+            Some((def, _)) => def.name.as_str(),
+            None => topic,
+        };
+
+        let (payload, schema_id) = match &self.serializer {
+            Some(serializer) => match serializer.deserialize(payload).await {
+                Err(err) => {
+                    error!(
+                        error = err.to_string(),
+                        topic = topic,
+                        "failure to deserialize message"
+                    );
+                    return Err(err);
+                }
+                Ok((id, data)) => (data, Some(id)),
+            },
+            None => (payload, None),
+        };
+
+        let Some(msg_type) = self.msg_type_strategy.msg_type(received, from, payload) else {
+            error!(topic = topic, "ignoring message - message with no msg_type");
+            return Ok(None);
+        };
+        let msg_type = msg_type.as_str();
+
+        if !self.dispatchers.contains_key(msg_type)
+            && !self.batch_dispatchers.contains_key(msg_type)
+        {
+            warn!(
+                topic = topic,
+                msg_type = msg_type,
+                "ignoring message - there is no handler registered for this msg_type",
+            );
+
+            return Ok(None);
+        }
+
+        let (ctx, mut headers) = explode(from, msg_type, tracer, received.headers());
+        if let Some(id) = schema_id {
+            headers
+                .get_or_insert_with(HashMap::new)
+                .insert(SCHEMA_ID_HEADER_KEY.to_owned(), id.to_string());
+        }
+
+        let consumer_msg =
+            ConsumerMessage::new(from, msg_type, payload, headers).with_offset(received.offset());
+
+        Ok(Some((ctx, consumer_msg)))
+    }
+
+    async fn exec<M: Message>(
+        &self,
+        received: &M,
+        ctx: &Context,
+        msg: &ConsumerMessage,
+    ) -> Result<(), MessagingError> {
+        let topic = received.topic();
+        let msg_type = msg.msg_type.as_str();
+
+        let Some(handler) = self.dispatchers.get(msg_type) else {
+            warn!(
+                topic = topic,
+                msg_type = msg_type,
+                "ignoring message - there is only a batch handler registered for this msg_type",
+            );
+
+            return Ok(());
+        };
+
+        match handler.exec(ctx, msg).await {
+            Err(err) => {
+                error!(
+                    error = err.to_string(),
+                    topic = topic,
+                    msg_type = msg_type,
+                    "error whiling processing message"
+                );
+                Err(err)
+            }
+            _ => {
+                debug!(
+                    topic = topic,
+                    msg_type = msg_type,
+                    "message processed succeffly"
+                );
+                Ok(())
+            }
+        }
+    }
+}
+
+fn is_delayed(delayed: &[DelayedPartition], topic: &str, partition: i32) -> bool {
+    delayed
+        .iter()
+        .any(|d| d.topic == topic && d.partition == partition)
+}
+
+pub(crate) fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+async fn shutdown_signal() {
+    let ctrl_c = tokio::signal::ctrl_c();
+
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = ctrl_c => {}
+                    _ = terminate.recv() => {}
+                }
+            }
+            Err(err) => {
+                warn!(error = err.to_string(), "failure to listen SIGTERM");
+                let _ = ctrl_c.await;
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    let _ = ctrl_c.await;
 }
 
-fn explode(
+fn explode<H: Headers>(
     topic: &str,
     msg_type: &str,
     tracer: &BoxedTracer,
-    kafka_headers: Option<&BorrowedHeaders>,
+    kafka_headers: Option<&H>,
 ) -> (Context, Option<HashMap<String, String>>) {
     let Some(headers) = kafka_headers else {
         return (otel::new_ctx(topic, msg_type, tracer), None);
@@ -0,0 +1,28 @@
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum KafkaError {
+    #[error("failure to fetch the cluster metadata")]
+    MetadataError,
+
+    #[error("failure to create a topic `{0}`")]
+    CreateTopicError(String),
+
+    #[error("failure to describe a topic `{0}`")]
+    DescribeTopicError(String),
+
+    #[error("failure to increase the partitions of a topic `{0}`")]
+    IncreasePartitionsError(String),
+
+    #[error("topology does not match the cluster `{0}`")]
+    TopologyMismatchError(String),
+
+    #[error("failure to look up the offset of `{0}`")]
+    OffsetLookupError(String),
+
+    #[error("failure to move the consumer of `{0}`")]
+    SeekError(String),
+
+    #[error("failure to assign the partitions to the consumer")]
+    AssignError,
+}
@@ -1,4 +1,14 @@
+pub mod batch;
+pub mod concurrency;
 pub mod connection;
+pub mod context;
 pub mod dispatcher;
+pub mod errors;
 pub mod otel;
 pub mod publisher;
+pub mod replay;
+pub mod retry;
+pub mod routing;
+pub mod schema_registry;
+pub mod topic;
+pub mod topology;
@@ -8,7 +8,7 @@ use opentelemetry::{
     },
     Context,
 };
-use rdkafka::message::{BorrowedHeaders, Header, Headers, OwnedHeaders};
+use rdkafka::message::{Header, Headers, OwnedHeaders};
 use tracing::error;
 
 const SUPPORTED_VERSION: u8 = 0;
@@ -63,7 +63,7 @@ pub fn inject_context(
         })
 }
 
-pub fn extract_context(kafka_headers: &BorrowedHeaders) -> Result<Context, ()> {
+pub fn extract_context<H: Headers>(kafka_headers: &H) -> Result<Context, ()> {
     let Some((header_value, stats)) = extract_trace_from_header(kafka_headers) else {
         return Err(());
     };
@@ -117,7 +117,7 @@ pub fn extract_context(kafka_headers: &BorrowedHeaders) -> Result<Context, ()> {
     Ok(Context::new().with_remote_span_context(span_context))
 }
 
-fn extract_trace_from_header<'e>(kafka_headers: &'e BorrowedHeaders) -> Option<(&'e str, &'e str)> {
+fn extract_trace_from_header<H: Headers>(kafka_headers: &H) -> Option<(&str, &str)> {
     let mut trace_parent = "";
     let mut trace_state = "";
     let mut founded = 0;
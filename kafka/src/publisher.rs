@@ -1,5 +1,5 @@
 use async_trait::async_trait;
-use configs::{Configs, DynamicConfigs, Environment};
+use configs::{Configs, DynamicConfigs};
 use messaging::{
     errors::MessagingError,
     publisher::{HeaderValues, PublishMessage, Publisher},
@@ -10,9 +10,11 @@ use opentelemetry::{
     Context,
 };
 use rdkafka::{
+    consumer::ConsumerGroupMetadata,
+    error::KafkaResult,
     message::{Header, OwnedHeaders, ToBytes},
-    producer::{FutureProducer, FutureRecord},
-    ClientConfig,
+    producer::{FutureProducer, FutureRecord, Producer},
+    ClientConfig, TopicPartitionList,
 };
 use std::{
     collections::HashMap,
@@ -21,7 +23,12 @@ use std::{
 };
 use tracing::error;
 
-use crate::otel;
+use crate::{
+    connection::{producer_config, with_properties},
+    otel,
+    routing::MSG_TYPE_HEADER_KEY,
+    schema_registry::SchemaSerializer,
+};
 
 /// LongInt
 pub const PARTITION_HEADER_KEY: &str = "kafka-partition";
@@ -32,9 +39,14 @@ pub const TIMESTAMP_HEADER_KEY: &str = "kafka-timestamp";
 /// LongLongUint
 pub const QUEUE_TIMEOUT_KEY: &str = "kafka-queue-timeout";
 
+///Default timeout of the transaction operations
+pub const DEFAULT_TRANSACTION_TIMEOUT: Duration = Duration::from_secs(30);
+
 pub struct KafkaPublisher {
     producer: Arc<FutureProducer>,
     tracer: BoxedTracer,
+    transactional: bool,
+    serializer: Option<Arc<SchemaSerializer>>,
 }
 
 impl KafkaPublisher {
@@ -42,26 +54,56 @@ impl KafkaPublisher {
     where
         T: DynamicConfigs,
     {
-        let log_level = match cfgs.app.env {
-            Environment::Local | Environment::Dev => rdkafka::config::RDKafkaLogLevel::Debug,
-            Environment::Staging | Environment::Prod => rdkafka::config::RDKafkaLogLevel::Info,
-        };
+        let mut config = producer_config(cfgs);
+        config.set("acks", cfgs.kafka.acks.clone());
+
+        Self::create(cfgs, config, false)
+    }
+
+    /// Retried sends are written once and in order, requires acks from all in-sync replicas.
+    pub fn new_idempotent<T>(cfgs: &Configs<T>) -> Result<Arc<Self>, MessagingError>
+    where
+        T: DynamicConfigs,
+    {
+        Self::create(cfgs, idempotent_config(cfgs), false)
+    }
+
+    /// Idempotent producer registered under `transactional_id`, every publish must happen
+    /// between `begin_transaction` and `commit_transaction`/`abort_transaction`.
+    /// The id must be stable across restarts so the broker can fence zombie instances.
+    pub fn new_transactional<T>(
+        cfgs: &Configs<T>,
+        transactional_id: &str,
+    ) -> Result<Arc<Self>, MessagingError>
+    where
+        T: DynamicConfigs,
+    {
+        let mut config = idempotent_config(cfgs);
+        config.set("transactional.id", transactional_id);
 
-        let producer = match ClientConfig::new()
-            .set(
-                "bootstrap.servers",
-                format!("{}:{}", cfgs.kafka.host, cfgs.kafka.port),
-            )
-            .set("client.id", cfgs.app.name.clone())
-            .set("acks", "1")
-            .set("message.timeout.ms", cfgs.kafka.timeout.to_string())
-            .set("security.protocol", cfgs.kafka.security_protocol.clone()) //security.protocol=SASL_PLAINTEXT or SASL_SSL
-            .set("sasl.mechanism", cfgs.kafka.sasl_mechanisms.clone()) //sasl.mechanism=PLAIN
-            .set("sasl.username", cfgs.kafka.user.clone())
-            .set("sasl.password", cfgs.kafka.password.clone())
-            .set_log_level(log_level)
-            .create::<FutureProducer>()
+        let publisher = Self::create(cfgs, config, true)?;
+
+        match publisher
+            .producer
+            .init_transactions(DEFAULT_TRANSACTION_TIMEOUT)
         {
+            Err(err) => {
+                error!(error = err.to_string(), "failure to init transactions");
+                Err(MessagingError::TransactionError(err.to_string()))
+            }
+            _ => Ok(publisher),
+        }
+    }
+
+    fn create<T>(
+        cfgs: &Configs<T>,
+        mut config: ClientConfig,
+        transactional: bool,
+    ) -> Result<Arc<Self>, MessagingError>
+    where
+        T: DynamicConfigs,
+    {
+        let producer = match with_properties(&mut config, cfgs).create::<FutureProducer>() {
             Ok(p) => Ok(p),
             Err(err) => {
                 error!(error = err.to_string(), "failure to create kafka producer");
@@ -72,8 +114,84 @@ impl KafkaPublisher {
         Ok(Arc::new(Self {
             producer: Arc::new(producer),
             tracer: global::tracer("kafka-publisher"),
+            transactional,
+            serializer: None,
         }))
     }
+
+    /// Publisher sharing the same producer whose payloads are serialized in the
+    /// Schema Registry wire format.
+    pub fn with_schema_registry(&self, serializer: Arc<SchemaSerializer>) -> Arc<Self> {
+        Arc::new(Self {
+            producer: self.producer.clone(),
+            tracer: global::tracer("kafka-publisher"),
+            transactional: self.transactional,
+            serializer: Some(serializer),
+        })
+    }
+
+    pub fn is_transactional(&self) -> bool {
+        self.transactional
+    }
+
+    pub fn begin_transaction(&self) -> Result<(), MessagingError> {
+        transaction_result("begin", self.producer.begin_transaction())
+    }
+
+    pub fn commit_transaction(&self) -> Result<(), MessagingError> {
+        transaction_result(
+            "commit",
+            self.producer
+                .commit_transaction(DEFAULT_TRANSACTION_TIMEOUT),
+        )
+    }
+
+    pub fn abort_transaction(&self) -> Result<(), MessagingError> {
+        transaction_result(
+            "abort",
+            self.producer.abort_transaction(DEFAULT_TRANSACTION_TIMEOUT),
+        )
+    }
+
+    /// Commits the consumed `offsets` of the consumer group `cgm` together with the open transaction
+    pub fn send_offsets_to_transaction(
+        &self,
+        offsets: &TopicPartitionList,
+        cgm: &ConsumerGroupMetadata,
+    ) -> Result<(), MessagingError> {
+        transaction_result(
+            "send offsets",
+            self.producer
+                .send_offsets_to_transaction(offsets, cgm, DEFAULT_TRANSACTION_TIMEOUT),
+        )
+    }
+}
+
+fn idempotent_config<T>(cfgs: &Configs<T>) -> ClientConfig
+where
+    T: DynamicConfigs,
+{
+    producer_config(cfgs)
+        .set("enable.idempotence", "true")
+        .set("acks", "all")
+        .to_owned()
+}
+
+fn transaction_result(operation: &str, result: KafkaResult<()>) -> Result<(), MessagingError> {
+    match result {
+        Err(err) => {
+            error!(
+                error = err.to_string(),
+                operation = operation,
+                "kafka transaction failure"
+            );
+            Err(MessagingError::TransactionError(format!(
+                "{} - {}",
+                operation, err
+            )))
+        }
+        _ => Ok(()),
+    }
 }
 
 #[async_trait]
@@ -82,11 +200,29 @@ impl Publisher for KafkaPublisher {
         let (partition, timestamp, queue_timeout) = self.publish_configs(&msg.headers);
         let headers = self.headers(ctx, msg);
 
+        let serialized = match &self.serializer {
+            Some(serializer) => Some(
+                serializer
+                    .serialize(&msg.to, &msg.msg_type, &msg.data)
+                    .await?,
+            ),
+            None => None,
+        };
+
+        let payload = match &serialized {
+            Some(data) => data.as_slice(),
+            None => msg.data.to_bytes(),
+        };
+
         let mut record = FutureRecord::to(&msg.to)
-            .key(&msg.key)
             .timestamp(timestamp)
             .headers(headers)
-            .payload(msg.data.to_bytes());
+            .payload(payload);
+
+        // records without key are spread across the partitions
+        if !msg.key.is_empty() {
+            record = record.key(&msg.key);
+        }
 
         if partition.is_some() {
             record.partition = partition;
@@ -156,16 +292,20 @@ impl KafkaPublisher {
     }
 
     fn headers(&self, ctx: &Context, msg: &PublishMessage) -> OwnedHeaders {
-        let Some(headers) = msg.headers.clone() else {
-            return OwnedHeaders::new();
-        };
-
         let mut kafka_headers = OwnedHeaders::new();
 
-        for (key, value) in headers {
+        if !msg.msg_type.is_empty() {
+            kafka_headers = kafka_headers.insert(Header {
+                key: MSG_TYPE_HEADER_KEY,
+                value: Some(&msg.msg_type),
+            });
+        }
+
+        for (key, value) in msg.headers.clone().unwrap_or_default() {
             if key.eq(PARTITION_HEADER_KEY)
                 || key.eq(TIMESTAMP_HEADER_KEY)
                 || key.eq(QUEUE_TIMEOUT_KEY)
+                || key.eq(MSG_TYPE_HEADER_KEY)
             {
                 continue;
             }
@@ -0,0 +1,180 @@
+use crate::errors::KafkaError;
+use rdkafka::{consumer::Consumer, Offset, TopicPartitionList};
+use std::{collections::HashMap, time::Duration};
+use tracing::error;
+
+///Timeout of the offset lookups done by the seek and replay APIs
+pub const DEFAULT_LOOKUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Position of a partition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeekPosition {
+    ///Oldest record still retained
+    Beginning,
+    ///Next record to be written
+    End,
+    Offset(i64),
+    ///First record written at or after the unix timestamp in milliseconds
+    Timestamp(i64),
+}
+
+/// Records handled by a replay, failed records are not sent to the retry topics
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReplaySummary {
+    pub records: u64,
+    pub failures: u64,
+}
+
+/// Resolves `position` to an offset, clamped to the records retained by the partition
+pub(crate) fn resolve_offset<C, X>(
+    consumer: &C,
+    topic: &str,
+    partition: i32,
+    position: SeekPosition,
+) -> Result<i64, KafkaError>
+where
+    C: Consumer<X>,
+    X: rdkafka::consumer::ConsumerContext,
+{
+    let (low, high) = match consumer.fetch_watermarks(topic, partition, DEFAULT_LOOKUP_TIMEOUT) {
+        Err(err) => {
+            error!(
+                error = err.to_string(),
+                topic = topic,
+                partition = partition,
+                "failure to fetch watermarks"
+            );
+            Err(KafkaError::OffsetLookupError(format!(
+                "{}/{}",
+                topic, partition
+            )))
+        }
+        Ok(w) => Ok(w),
+    }?;
+
+    let offset = match position {
+        SeekPosition::Beginning => low,
+        SeekPosition::End => high,
+        SeekPosition::Offset(offset) => offset,
+        SeekPosition::Timestamp(timestamp) => {
+            let mut tpl = TopicPartitionList::new();
+            let _ = tpl.add_partition_offset(topic, partition, Offset::Offset(timestamp));
+
+            let offsets = match consumer.offsets_for_times(tpl, DEFAULT_LOOKUP_TIMEOUT) {
+                Err(err) => {
+                    error!(
+                        error = err.to_string(),
+                        topic = topic,
+                        partition = partition,
+                        "failure to look up offset by timestamp"
+                    );
+                    Err(KafkaError::OffsetLookupError(format!(
+                        "{}/{}",
+                        topic, partition
+                    )))
+                }
+                Ok(o) => Ok(o),
+            }?;
+
+            // no record at or after the timestamp resolves to the end of the partition
+            match offsets
+                .find_partition(topic, partition)
+                .map(|elem| elem.offset())
+            {
+                Some(Offset::Offset(offset)) => offset,
+                _ => high,
+            }
+        }
+    };
+
+    Ok(offset.clamp(low, high))
+}
+
+/// Offset ranges `[start, end)` of a replay, partitions are done once their end is reached
+#[derive(Debug, Default)]
+pub(crate) struct ReplayProgress {
+    ranges: HashMap<(String, i32), (i64, i64)>,
+}
+
+impl ReplayProgress {
+    pub(crate) fn new(ranges: Vec<(String, i32, i64, i64)>) -> ReplayProgress {
+        ReplayProgress {
+            ranges: ranges
+                .into_iter()
+                .filter(|(_, _, start, end)| start < end)
+                .map(|(topic, partition, start, end)| ((topic, partition), (start, end)))
+                .collect(),
+        }
+    }
+
+    ///Assignment starting every partition with records to replay at its start offset
+    pub(crate) fn assignment(&self) -> TopicPartitionList {
+        let mut tpl = TopicPartitionList::new();
+
+        for ((topic, partition), (start, _)) in &self.ranges {
+            let _ = tpl.add_partition_offset(topic, *partition, Offset::Offset(*start));
+        }
+
+        tpl
+    }
+
+    ///Whether the record is part of the replay, the partition is done after its last record
+    pub(crate) fn accept(&mut self, topic: &str, partition: i32, offset: i64) -> bool {
+        let key = (topic.to_owned(), partition);
+
+        let Some((start, end)) = self.ranges.get(&key).copied() else {
+            return false;
+        };
+
+        if offset + 1 >= end {
+            self.ranges.remove(&key);
+        }
+
+        offset >= start && offset < end
+    }
+
+    ///Completes the partition once the consumer position passed its end, the last offsets
+    ///of a range may never be delivered when they are transaction markers
+    pub(crate) fn reached(&mut self, topic: &str, partition: i32, position: i64) {
+        let key = (topic.to_owned(), partition);
+
+        if self
+            .ranges
+            .get(&key)
+            .is_some_and(|(_, end)| position >= *end)
+        {
+            self.ranges.remove(&key);
+        }
+    }
+
+    pub(crate) fn is_done(&self) -> bool {
+        self.ranges.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_track_replay_ranges() {
+        let mut progress = ReplayProgress::new(vec![
+            ("orders".to_owned(), 0, 10, 12),
+            ("orders".to_owned(), 1, 5, 5),
+        ]);
+
+        assert_eq!(progress.assignment().count(), 1);
+        assert!(progress.accept("orders", 0, 10));
+        assert!(!progress.accept("orders", 1, 5));
+        assert!(!progress.is_done());
+        assert!(progress.accept("orders", 0, 11));
+        assert!(progress.is_done());
+        assert!(!progress.accept("orders", 0, 12));
+
+        let mut progress = ReplayProgress::new(vec![("orders".to_owned(), 0, 10, 12)]);
+        progress.reached("orders", 0, 11);
+        assert!(!progress.is_done());
+        progress.reached("orders", 0, 12);
+        assert!(progress.is_done());
+    }
+}
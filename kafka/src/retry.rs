@@ -0,0 +1,210 @@
+use crate::topic::TopicDefinition;
+use rdkafka::message::{Header, Headers, OwnedHeaders};
+use rdkafka::Message;
+use std::{collections::HashMap, str, time::Duration};
+
+pub const RETRY_ATTEMPT_HEADER_KEY: &str = "kafka-retry-attempt";
+
+/// Unix timestamp in milliseconds before which the retry record must not be handled
+pub const RETRY_NOT_BEFORE_HEADER_KEY: &str = "kafka-retry-not-before";
+
+pub const ORIGINAL_TOPIC_HEADER_KEY: &str = "kafka-original-topic";
+pub const ORIGINAL_PARTITION_HEADER_KEY: &str = "kafka-original-partition";
+pub const ORIGINAL_OFFSET_HEADER_KEY: &str = "kafka-original-offset";
+
+/// Handler error of the last attempt, only present in dead-letter records
+pub const ERROR_HEADER_KEY: &str = "kafka-error";
+
+///Delay before consuming again a record that could not be handled nor republished
+pub const DEFAULT_FAILURE_BACKOFF: Duration = Duration::from_millis(500);
+///Upper bound of the failure backoff, which doubles on each consecutive failure
+pub const MAX_FAILURE_BACKOFF: Duration = Duration::from_secs(30);
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum FailureRoute {
+    Retry {
+        topic: String,
+        attempt: usize,
+        not_before: i64,
+    },
+    DeadLetter {
+        topic: String,
+        attempts: usize,
+    },
+    Discard,
+}
+
+/// Where a record that failed `attempt` times must be republished
+pub(crate) fn failure_route(def: &TopicDefinition, attempt: usize, now: i64) -> FailureRoute {
+    if let Some(delay) = def.retry_delays.get(attempt) {
+        return FailureRoute::Retry {
+            topic: def.retry_topic_name(attempt + 1),
+            attempt: attempt + 1,
+            not_before: now + delay.as_millis() as i64,
+        };
+    }
+
+    match &def.dlt_name {
+        Some(dlt) => FailureRoute::DeadLetter {
+            topic: dlt.clone(),
+            attempts: attempt + 1,
+        },
+        None => FailureRoute::Discard,
+    }
+}
+
+/// Copies the record headers, replacing the retry bookkeeping with `extra`.
+/// The original topic, partition and offset are kept from the first failure.
+pub(crate) fn republish_headers<M: Message>(
+    received: &M,
+    extra: &[(&str, String)],
+) -> OwnedHeaders {
+    let mut headers = OwnedHeaders::new();
+
+    let original_topic = header_value(received, ORIGINAL_TOPIC_HEADER_KEY)
+        .unwrap_or_else(|| received.topic().to_owned());
+    let original_partition = header_value(received, ORIGINAL_PARTITION_HEADER_KEY)
+        .unwrap_or_else(|| received.partition().to_string());
+    let original_offset = header_value(received, ORIGINAL_OFFSET_HEADER_KEY)
+        .unwrap_or_else(|| received.offset().to_string());
+
+    if let Some(borrowed) = received.headers() {
+        for h in borrowed.iter() {
+            if is_retry_header(h.key) {
+                continue;
+            }
+
+            headers = headers.insert(Header {
+                key: h.key,
+                value: h.value,
+            });
+        }
+    }
+
+    let entries = [
+        (ORIGINAL_TOPIC_HEADER_KEY, original_topic),
+        (ORIGINAL_PARTITION_HEADER_KEY, original_partition),
+        (ORIGINAL_OFFSET_HEADER_KEY, original_offset),
+    ];
+
+    for (key, value) in entries.iter().chain(extra.iter()) {
+        headers = headers.insert(Header {
+            key,
+            value: Some(value.as_bytes()),
+        });
+    }
+
+    headers
+}
+
+pub(crate) fn retry_not_before<M: Message>(received: &M) -> Option<i64> {
+    header_value(received, RETRY_NOT_BEFORE_HEADER_KEY)?
+        .parse()
+        .ok()
+}
+
+pub(crate) fn header_value<M: Message>(received: &M, key: &str) -> Option<String> {
+    let headers = received.headers()?;
+
+    headers
+        .iter()
+        .find(|h| h.key == key)
+        .and_then(|h| h.value)
+        .and_then(|v| str::from_utf8(v).ok())
+        .map(|v| v.to_owned())
+}
+
+/// Consecutive failures per partition, spacing out the attempts to consume again a
+/// record that can not be republished (e.g. the retry or dead-letter topic is missing)
+#[derive(Debug, Default)]
+pub(crate) struct FailureBackoff {
+    failures: HashMap<(String, i32), u32>,
+}
+
+impl FailureBackoff {
+    ///Delay before the partition is consumed again
+    pub(crate) fn failed(&mut self, topic: &str, partition: i32) -> Duration {
+        let failures = self
+            .failures
+            .entry((topic.to_owned(), partition))
+            .or_default();
+        let delay = DEFAULT_FAILURE_BACKOFF.saturating_mul(2u32.saturating_pow(*failures));
+        *failures = failures.saturating_add(1);
+
+        delay.min(MAX_FAILURE_BACKOFF)
+    }
+
+    ///Resets the backoff once the partition moved forward
+    pub(crate) fn forget(&mut self, topic: &str, partition: i32) {
+        self.failures.remove(&(topic.to_owned(), partition));
+    }
+}
+
+fn is_retry_header(key: &str) -> bool {
+    key == RETRY_ATTEMPT_HEADER_KEY
+        || key == RETRY_NOT_BEFORE_HEADER_KEY
+        || key == ORIGINAL_TOPIC_HEADER_KEY
+        || key == ORIGINAL_PARTITION_HEADER_KEY
+        || key == ORIGINAL_OFFSET_HEADER_KEY
+        || key == ERROR_HEADER_KEY
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_route_failures_through_retry_topics_then_dlt() {
+        let def = TopicDefinition::new("orders")
+            .with_backoff(&[Duration::from_secs(1), Duration::from_secs(10)])
+            .with_dlt();
+
+        assert_eq!(
+            failure_route(&def, 0, 1000),
+            FailureRoute::Retry {
+                topic: "orders-retry-1".to_owned(),
+                attempt: 1,
+                not_before: 2000,
+            }
+        );
+        assert_eq!(
+            failure_route(&def, 1, 1000),
+            FailureRoute::Retry {
+                topic: "orders-retry-2".to_owned(),
+                attempt: 2,
+                not_before: 11000,
+            }
+        );
+        assert_eq!(
+            failure_route(&def, 2, 1000),
+            FailureRoute::DeadLetter {
+                topic: "orders-dlt".to_owned(),
+                attempts: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn should_discard_without_dlt() {
+        let def = TopicDefinition::new("orders");
+
+        assert_eq!(failure_route(&def, 0, 0), FailureRoute::Discard);
+    }
+
+    #[test]
+    fn should_cap_failure_backoff() {
+        let mut backoff = FailureBackoff::default();
+
+        assert_eq!(backoff.failed("orders", 0), DEFAULT_FAILURE_BACKOFF);
+        assert_eq!(backoff.failed("orders", 0), DEFAULT_FAILURE_BACKOFF * 2);
+        assert_eq!(backoff.failed("orders", 1), DEFAULT_FAILURE_BACKOFF);
+
+        for _ in 0..10 {
+            backoff.failed("orders", 0);
+        }
+        assert_eq!(backoff.failed("orders", 0), MAX_FAILURE_BACKOFF);
+
+        backoff.forget("orders", 0);
+        assert_eq!(backoff.failed("orders", 0), DEFAULT_FAILURE_BACKOFF);
+    }
+}
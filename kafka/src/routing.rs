@@ -0,0 +1,110 @@
+use crate::retry::header_value;
+use rdkafka::Message;
+use std::str;
+
+/// Header written by the `KafkaPublisher` with the `msg_type` of the record
+pub const MSG_TYPE_HEADER_KEY: &str = "kafka-msg-type";
+
+/// Where the dispatcher reads the `msg_type` used to find the handler of a record
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum MsgTypeStrategy {
+    ///`kafka-msg-type` header, records without it fall back to the key
+    #[default]
+    Header,
+    ///Record key, the partitioning of the topic follows the message types
+    Key,
+    ///Name of the main topic, one handler per topic
+    Topic,
+    ///Top-level string field of a JSON payload
+    PayloadField(String),
+}
+
+impl MsgTypeStrategy {
+    /// `topic` is the main topic of the record, `payload` the payload without
+    /// the Schema Registry framing.
+    pub(crate) fn msg_type<M: Message>(
+        &self,
+        received: &M,
+        topic: &str,
+        payload: &[u8],
+    ) -> Option<String> {
+        match self {
+            MsgTypeStrategy::Header => {
+                header_value(received, MSG_TYPE_HEADER_KEY).or_else(|| key(received))
+            }
+            MsgTypeStrategy::Key => key(received),
+            MsgTypeStrategy::Topic => Some(topic.to_owned()),
+            MsgTypeStrategy::PayloadField(field) => {
+                let value = serde_json::from_slice::<serde_json::Value>(payload).ok()?;
+                value.get(field)?.as_str().map(|v| v.to_owned())
+            }
+        }
+    }
+}
+
+fn key<M: Message>(received: &M) -> Option<String> {
+    received
+        .key()
+        .and_then(|k| str::from_utf8(k).ok())
+        .map(|k| k.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rdkafka::{
+        message::{Header, OwnedHeaders, OwnedMessage},
+        Timestamp,
+    };
+
+    fn record(key: Option<&str>, msg_type: Option<&str>, payload: &str) -> OwnedMessage {
+        let headers = msg_type.map(|t| {
+            OwnedHeaders::new().insert(Header {
+                key: MSG_TYPE_HEADER_KEY,
+                value: Some(t),
+            })
+        });
+
+        OwnedMessage::new(
+            Some(payload.as_bytes().to_vec()),
+            key.map(|k| k.as_bytes().to_vec()),
+            "orders.retry.1".to_owned(),
+            Timestamp::NotAvailable,
+            0,
+            0,
+            headers,
+        )
+    }
+
+    #[test]
+    fn should_resolve_msg_type() {
+        let payload = r#"{"type":"OrderCreated"}"#;
+        let with_header = record(Some("order-1"), Some("OrderCreated"), payload);
+        let without_header = record(Some("OrderCanceled"), None, payload);
+
+        let header = MsgTypeStrategy::Header;
+        assert_eq!(
+            header.msg_type(&with_header, "orders", payload.as_bytes()),
+            Some("OrderCreated".to_owned())
+        );
+        assert_eq!(
+            header.msg_type(&without_header, "orders", payload.as_bytes()),
+            Some("OrderCanceled".to_owned())
+        );
+        assert_eq!(
+            MsgTypeStrategy::Key.msg_type(&with_header, "orders", payload.as_bytes()),
+            Some("order-1".to_owned())
+        );
+        assert_eq!(
+            MsgTypeStrategy::Topic.msg_type(&with_header, "orders", payload.as_bytes()),
+            Some("orders".to_owned())
+        );
+
+        let field = MsgTypeStrategy::PayloadField("type".to_owned());
+        assert_eq!(
+            field.msg_type(&with_header, "orders", payload.as_bytes()),
+            Some("OrderCreated".to_owned())
+        );
+        assert_eq!(field.msg_type(&with_header, "orders", b"not json"), None);
+    }
+}
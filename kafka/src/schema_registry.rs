@@ -0,0 +1,496 @@
+use messaging::errors::MessagingError;
+use reqwest::{Client, RequestBuilder, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+use tracing::{debug, error};
+
+///First byte of the Confluent wire format
+pub const MAGIC_BYTE: u8 = 0;
+
+///Schema id of the record, added to the `ConsumerMessage` headers
+pub const SCHEMA_ID_HEADER_KEY: &str = "kafka-schema-id";
+
+const SCHEMA_REGISTRY_CONTENT_TYPE: &str = "application/vnd.schemaregistry.v1+json";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum SchemaType {
+    #[default]
+    Avro,
+    Protobuf,
+    Json,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Schema {
+    ///The registry omits the type of Avro schemas
+    #[serde(rename = "schemaType", default)]
+    pub schema_type: SchemaType,
+    pub schema: String,
+}
+
+impl Schema {
+    pub fn new(schema_type: SchemaType, schema: &str) -> Schema {
+        Schema {
+            schema_type,
+            schema: schema.to_owned(),
+        }
+    }
+}
+
+///How the subject of a record is named, the record name is the message `msg_type`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SubjectNameStrategy {
+    ///`<topic>-value`
+    #[default]
+    TopicName,
+    ///`<msg_type>`
+    RecordName,
+    ///`<topic>-<msg_type>`
+    TopicRecordName,
+}
+
+impl SubjectNameStrategy {
+    pub fn subject(&self, topic: &str, msg_type: &str) -> String {
+        match self {
+            SubjectNameStrategy::TopicName => format!("{}-value", topic),
+            SubjectNameStrategy::RecordName => msg_type.to_owned(),
+            SubjectNameStrategy::TopicRecordName => format!("{}-{}", topic, msg_type),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct SchemaIdResponse {
+    id: u32,
+}
+
+#[derive(Deserialize)]
+struct CompatibilityResponse {
+    is_compatible: bool,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    message: String,
+}
+
+/// Schema Registry REST client, schemas and ids are cached for the life of the client.
+pub struct SchemaRegistryClient {
+    http: Client,
+    url: String,
+    credentials: Option<(String, String)>,
+    ids: RwLock<HashMap<u32, Schema>>,
+    subjects: RwLock<HashMap<(String, Schema), u32>>,
+}
+
+impl SchemaRegistryClient {
+    pub fn new(url: &str) -> SchemaRegistryClient {
+        SchemaRegistryClient {
+            http: Client::new(),
+            url: url.trim_end_matches('/').to_owned(),
+            credentials: None,
+            ids: RwLock::new(HashMap::new()),
+            subjects: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn with_basic_auth(mut self, user: &str, password: &str) -> Self {
+        self.credentials = Some((user.to_owned(), password.to_owned()));
+        self
+    }
+
+    ///Registers `schema` under `subject`, an already registered schema returns its id
+    pub async fn register(&self, subject: &str, schema: &Schema) -> Result<u32, MessagingError> {
+        if let Some(id) = self.cached_id(subject, schema) {
+            return Ok(id);
+        }
+
+        let res: SchemaIdResponse = self
+            .send(
+                subject,
+                self.http
+                    .post(format!("{}/subjects/{}/versions", self.url, subject))
+                    .json(schema),
+            )
+            .await?;
+
+        debug!(subject = subject, id = res.id, "schema registered");
+
+        Ok(self.cache(subject, schema, res.id))
+    }
+
+    ///Id of `schema` when it is already registered under `subject`
+    pub async fn lookup(&self, subject: &str, schema: &Schema) -> Result<u32, MessagingError> {
+        if let Some(id) = self.cached_id(subject, schema) {
+            return Ok(id);
+        }
+
+        let res: SchemaIdResponse = self
+            .send(
+                subject,
+                self.http
+                    .post(format!("{}/subjects/{}", self.url, subject))
+                    .json(schema),
+            )
+            .await?;
+
+        Ok(self.cache(subject, schema, res.id))
+    }
+
+    pub async fn schema(&self, id: u32) -> Result<Schema, MessagingError> {
+        if let Some(schema) = self.ids.read().ok().and_then(|ids| ids.get(&id).cloned()) {
+            return Ok(schema);
+        }
+
+        let schema: Schema = self
+            .send(
+                &id.to_string(),
+                self.http.get(format!("{}/schemas/ids/{}", self.url, id)),
+            )
+            .await?;
+
+        if let Ok(mut ids) = self.ids.write() {
+            ids.insert(id, schema.clone());
+        }
+
+        Ok(schema)
+    }
+
+    ///Checks `schema` against the latest version registered under `subject`
+    pub async fn is_compatible(
+        &self,
+        subject: &str,
+        schema: &Schema,
+    ) -> Result<bool, MessagingError> {
+        let res: CompatibilityResponse = self
+            .send(
+                subject,
+                self.http
+                    .post(format!(
+                        "{}/compatibility/subjects/{}/versions/latest",
+                        self.url, subject
+                    ))
+                    .json(schema),
+            )
+            .await?;
+
+        Ok(res.is_compatible)
+    }
+
+    fn cached_id(&self, subject: &str, schema: &Schema) -> Option<u32> {
+        self.subjects
+            .read()
+            .ok()?
+            .get(&(subject.to_owned(), schema.clone()))
+            .copied()
+    }
+
+    fn cache(&self, subject: &str, schema: &Schema, id: u32) -> u32 {
+        if let Ok(mut subjects) = self.subjects.write() {
+            subjects.insert((subject.to_owned(), schema.clone()), id);
+        }
+        if let Ok(mut ids) = self.ids.write() {
+            ids.insert(id, schema.clone());
+        }
+
+        id
+    }
+
+    async fn send<T: DeserializeOwned>(
+        &self,
+        subject: &str,
+        req: RequestBuilder,
+    ) -> Result<T, MessagingError> {
+        let mut req = req.header(reqwest::header::ACCEPT, SCHEMA_REGISTRY_CONTENT_TYPE);
+        if let Some((user, password)) = &self.credentials {
+            req = req.basic_auth(user, Some(password));
+        }
+
+        let res = match req.send().await {
+            Err(err) => {
+                error!(
+                    error = err.to_string(),
+                    "failure to reach the schema registry"
+                );
+                Err(MessagingError::SchemaRegistryError(err.to_string()))
+            }
+            Ok(r) => Ok(r),
+        }?;
+
+        let status = res.status();
+        if !status.is_success() {
+            let message = match res.json::<ErrorResponse>().await {
+                Ok(body) => body.message,
+                Err(_) => status.to_string(),
+            };
+
+            error!(
+                status = status.as_u16(),
+                subject = subject,
+                message = message,
+                "schema registry request failure"
+            );
+
+            return match status {
+                StatusCode::CONFLICT => Err(MessagingError::IncompatibleSchemaError(format!(
+                    "{} - {}",
+                    subject, message
+                ))),
+                _ => Err(MessagingError::SchemaRegistryError(format!(
+                    "{} - {}",
+                    subject, message
+                ))),
+            };
+        }
+
+        match res.json::<T>().await {
+            Err(err) => {
+                error!(error = err.to_string(), "invalid schema registry response");
+                Err(MessagingError::SchemaRegistryError(err.to_string()))
+            }
+            Ok(body) => Ok(body),
+        }
+    }
+}
+
+/// Frames the payloads with the Confluent wire format: magic byte, big-endian schema id
+/// and, for Protobuf, the message indexes. The payload itself must already be encoded
+/// with the schema of its `msg_type`.
+pub struct SchemaSerializer {
+    client: Arc<SchemaRegistryClient>,
+    strategy: SubjectNameStrategy,
+    schemas: HashMap<String, Schema>,
+    auto_register: bool,
+}
+
+impl SchemaSerializer {
+    pub fn new(client: Arc<SchemaRegistryClient>) -> SchemaSerializer {
+        SchemaSerializer {
+            client,
+            strategy: SubjectNameStrategy::TopicName,
+            schemas: HashMap::new(),
+            auto_register: true,
+        }
+    }
+
+    pub fn strategy(mut self, strategy: SubjectNameStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    ///Schema of the messages published with `msg_type`
+    pub fn schema(mut self, msg_type: &str, schema: Schema) -> Self {
+        self.schemas.insert(msg_type.to_owned(), schema);
+        self
+    }
+
+    ///When disabled the schemas must be registered beforehand. Default: true
+    pub fn auto_register(mut self, auto_register: bool) -> Self {
+        self.auto_register = auto_register;
+        self
+    }
+
+    pub async fn serialize(
+        &self,
+        topic: &str,
+        msg_type: &str,
+        payload: &[u8],
+    ) -> Result<Vec<u8>, MessagingError> {
+        let Some(schema) = self.schemas.get(msg_type) else {
+            error!(msg_type = msg_type, "there is no schema for this msg_type");
+            return Err(MessagingError::SchemaRegistryError(msg_type.to_owned()));
+        };
+
+        let subject = self.strategy.subject(topic, msg_type);
+
+        let id = match self.auto_register {
+            true => self.client.register(&subject, schema).await,
+            false => self.client.lookup(&subject, schema).await,
+        }?;
+
+        Ok(encode(id, schema.schema_type, payload))
+    }
+
+    ///Schema id and encoded payload of a wire format record
+    pub async fn deserialize<'p>(&self, data: &'p [u8]) -> Result<(u32, &'p [u8]), MessagingError> {
+        let (id, payload) = decode_header(data)?;
+        let schema = self.client.schema(id).await?;
+
+        match schema.schema_type {
+            SchemaType::Protobuf => Ok((id, skip_message_indexes(payload)?)),
+            _ => Ok((id, payload)),
+        }
+    }
+}
+
+pub(crate) fn encode(id: u32, schema_type: SchemaType, payload: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(payload.len() + 6);
+    data.push(MAGIC_BYTE);
+    data.extend_from_slice(&id.to_be_bytes());
+
+    // message indexes [0], the first message of the schema
+    if schema_type == SchemaType::Protobuf {
+        data.push(0);
+    }
+
+    data.extend_from_slice(payload);
+    data
+}
+
+pub(crate) fn decode_header(data: &[u8]) -> Result<(u32, &[u8]), MessagingError> {
+    if data.len() < 5 || data[0] != MAGIC_BYTE {
+        error!("payload is not in the schema registry wire format");
+        return Err(MessagingError::DeserializingError);
+    }
+
+    let id = u32::from_be_bytes([data[1], data[2], data[3], data[4]]);
+
+    Ok((id, &data[5..]))
+}
+
+fn skip_message_indexes(data: &[u8]) -> Result<&[u8], MessagingError> {
+    let (count, mut data) = read_varint(data)?;
+
+    for _ in 0..count {
+        data = read_varint(data)?.1;
+    }
+
+    Ok(data)
+}
+
+///Zigzag encoded varint
+fn read_varint(data: &[u8]) -> Result<(i64, &[u8]), MessagingError> {
+    let mut value: u64 = 0;
+
+    for (i, byte) in data.iter().enumerate().take(10) {
+        value |= ((byte & 0x7f) as u64) << (7 * i);
+
+        if byte & 0x80 == 0 {
+            let decoded = ((value >> 1) as i64) ^ -((value & 1) as i64);
+            return Ok((decoded, &data[i + 1..]));
+        }
+    }
+
+    error!("invalid protobuf message indexes");
+    Err(MessagingError::DeserializingError)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    /// Local registry answering `(method, path)` with a fixed status and body
+    async fn mock_registry(routes: Vec<(&'static str, &'static str, u16, &'static str)>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            loop {
+                let Ok((mut stream, _)) = listener.accept().await else {
+                    return;
+                };
+
+                let mut buf = vec![0; 8192];
+                let n = stream.read(&mut buf).await.unwrap_or_default();
+                let request = String::from_utf8_lossy(&buf[..n]).to_string();
+                let mut parts = request.split_whitespace();
+                let (method, path) = (
+                    parts.next().unwrap_or_default(),
+                    parts.next().unwrap_or_default(),
+                );
+
+                let (status, body) = routes
+                    .iter()
+                    .find(|(m, p, _, _)| *m == method && *p == path)
+                    .map(|(_, _, s, b)| (*s, *b))
+                    .unwrap_or((404, r#"{"error_code":40401,"message":"not found"}"#));
+
+                let response = format!(
+                    "HTTP/1.1 {} MOCK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn should_serialize_and_deserialize_wire_format() {
+        let url = mock_registry(vec![
+            (
+                "POST",
+                "/subjects/orders-value/versions",
+                200,
+                r#"{"id":42}"#,
+            ),
+            (
+                "GET",
+                "/schemas/ids/42",
+                200,
+                r#"{"schema":"{\"type\":\"object\"}","schemaType":"JSON"}"#,
+            ),
+        ])
+        .await;
+
+        let serializer = SchemaSerializer::new(Arc::new(SchemaRegistryClient::new(&url))).schema(
+            "order-created",
+            Schema::new(SchemaType::Json, r#"{"type":"object"}"#),
+        );
+
+        let data = serializer
+            .serialize("orders", "order-created", b"{}")
+            .await
+            .unwrap();
+        assert_eq!(data, vec![0, 0, 0, 0, 42, b'{', b'}']);
+
+        let (id, payload) = serializer.deserialize(&data).await.unwrap();
+        assert_eq!(id, 42);
+        assert_eq!(payload, b"{}");
+    }
+
+    #[tokio::test]
+    async fn should_surface_incompatible_schema() {
+        let url = mock_registry(vec![(
+            "POST",
+            "/subjects/orders-value/versions",
+            409,
+            r#"{"error_code":409,"message":"incompatible schema"}"#,
+        )])
+        .await;
+
+        let serializer = SchemaSerializer::new(Arc::new(SchemaRegistryClient::new(&url))).schema(
+            "order-created",
+            Schema::new(SchemaType::Avro, r#""string""#),
+        );
+
+        assert_eq!(
+            serializer.serialize("orders", "order-created", b"").await,
+            Err(MessagingError::IncompatibleSchemaError(
+                "orders-value - incompatible schema".to_owned()
+            ))
+        );
+    }
+
+    #[test]
+    fn should_skip_protobuf_message_indexes() {
+        let data = encode(7, SchemaType::Protobuf, b"msg");
+        let (id, payload) = decode_header(&data).unwrap();
+
+        assert_eq!(id, 7);
+        assert_eq!(skip_message_indexes(payload).unwrap(), b"msg");
+        // two indexes [1, 2] zigzag encoded
+        assert_eq!(skip_message_indexes(&[4, 2, 4, 9]).unwrap(), &[9]);
+    }
+}
@@ -0,0 +1,156 @@
+use std::{collections::BTreeMap, fmt::Display, time::Duration};
+
+pub const TOPIC_CONFIG_RETENTION_MS: &str = "retention.ms";
+pub const TOPIC_CONFIG_CLEANUP_POLICY: &str = "cleanup.policy";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CleanupPolicy {
+    Delete,
+    Compact,
+    CompactDelete,
+}
+
+impl Display for CleanupPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CleanupPolicy::Delete => write!(f, "delete"),
+            CleanupPolicy::Compact => write!(f, "compact"),
+            CleanupPolicy::CompactDelete => write!(f, "compact,delete"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TopicDefinition {
+    pub(crate) name: String,
+    pub(crate) retry_delays: Vec<Duration>,
+    pub(crate) dlt_name: Option<String>,
+    pub(crate) partitions: Option<i32>,
+    pub(crate) replication: Option<i32>,
+    pub(crate) configs: BTreeMap<String, String>,
+}
+
+impl TopicDefinition {
+    pub fn new(name: &str) -> TopicDefinition {
+        TopicDefinition {
+            name: name.to_owned(),
+            retry_delays: vec![],
+            dlt_name: None,
+            partitions: None,
+            replication: None,
+            configs: BTreeMap::new(),
+        }
+    }
+
+    ///Topics are created with the broker default when not set
+    pub fn partitions(mut self, partitions: i32) -> Self {
+        self.partitions = Some(partitions);
+        self
+    }
+
+    ///Topics are created with the broker default when not set
+    pub fn replication(mut self, replication: i32) -> Self {
+        self.replication = Some(replication);
+        self
+    }
+
+    pub fn retention(self, retention: Duration) -> Self {
+        self.config(
+            TOPIC_CONFIG_RETENTION_MS,
+            &retention.as_millis().to_string(),
+        )
+    }
+
+    pub fn cleanup_policy(self, policy: CleanupPolicy) -> Self {
+        self.config(TOPIC_CONFIG_CLEANUP_POLICY, &policy.to_string())
+    }
+
+    pub fn compacted(self) -> Self {
+        self.cleanup_policy(CleanupPolicy::Compact)
+    }
+
+    ///Any topic level config, e.g. `min.insync.replicas`
+    pub fn config(mut self, key: &str, value: &str) -> Self {
+        self.configs.insert(key.to_owned(), value.to_owned());
+        self
+    }
+
+    ///Failed records are sent to `<name>-dlt` once the retries are exhausted
+    pub fn with_dlt(mut self) -> Self {
+        self.dlt_name = Some(format!("{}-dlt", self.name));
+        self
+    }
+
+    ///`retries` retry topics `<name>-retry-N`, each consumed `delay` after the failure
+    pub fn with_retry(self, delay: Duration, retries: usize) -> Self {
+        self.with_backoff(&vec![delay; retries])
+    }
+
+    ///One retry topic per delay, the n-th failure is consumed from `<name>-retry-<n>`
+    pub fn with_backoff(mut self, delays: &[Duration]) -> Self {
+        self.retry_delays = delays.to_vec();
+        self
+    }
+
+    pub(crate) fn retry_topic_name(&self, attempt: usize) -> String {
+        format!("{}-retry-{}", self.name, attempt)
+    }
+
+    ///Main topic followed by its retry topics
+    pub(crate) fn topics(&self) -> Vec<String> {
+        let mut topics = vec![self.name.clone()];
+        for attempt in 1..=self.retry_delays.len() {
+            topics.push(self.retry_topic_name(attempt));
+        }
+
+        topics
+    }
+
+    ///Every topic declared by the topology: main, retry and dead-letter topics
+    pub(crate) fn declarations(&self) -> Vec<String> {
+        let mut topics = self.topics();
+        if let Some(dlt) = &self.dlt_name {
+            topics.push(dlt.clone());
+        }
+
+        topics
+    }
+
+    ///Number of failures already handled for records read from `topic`
+    pub(crate) fn attempt(&self, topic: &str) -> Option<usize> {
+        if topic == self.name {
+            return Some(0);
+        }
+
+        (1..=self.retry_delays.len()).find(|attempt| self.retry_topic_name(*attempt) == topic)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_name_retry_and_dlt_topics() {
+        let def = TopicDefinition::new("orders")
+            .with_retry(Duration::from_secs(5), 2)
+            .with_dlt();
+
+        assert_eq!(
+            def.topics(),
+            vec!["orders", "orders-retry-1", "orders-retry-2"]
+        );
+        assert_eq!(def.dlt_name, Some("orders-dlt".to_owned()));
+    }
+
+    #[test]
+    fn should_resolve_attempt_from_topic() {
+        let def = TopicDefinition::new("orders")
+            .with_backoff(&[Duration::from_secs(1), Duration::from_secs(60)]);
+
+        assert_eq!(def.attempt("orders"), Some(0));
+        assert_eq!(def.attempt("orders-retry-2"), Some(2));
+        assert_eq!(def.attempt("orders-retry-3"), None);
+        assert_eq!(def.attempt("payments"), None);
+    }
+}
@@ -0,0 +1,418 @@
+use crate::{errors::KafkaError, topic::TopicDefinition};
+use rdkafka::{
+    admin::{
+        AdminClient, AdminOptions, NewPartitions, NewTopic, ResourceSpecifier, TopicReplication,
+    },
+    client::DefaultClientContext,
+    types::RDKafkaErrorCode,
+};
+use std::{collections::HashMap, fmt::Display, sync::Arc, time::Duration};
+use tracing::{debug, error, warn};
+
+pub const DEFAULT_ADMIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Topic configs are only verified, `install` never alters an existing topic
+/// besides increasing its partitions when enabled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TopicChange {
+    CreateTopic(String),
+    IncreasePartitions { name: String, from: i32, to: i32 },
+    TopicMismatch { name: String, reason: String },
+}
+
+impl Display for TopicChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TopicChange::CreateTopic(name) => write!(f, "create topic `{}`", name),
+            TopicChange::IncreasePartitions { name, from, to } => write!(
+                f,
+                "increase partitions of topic `{}` from {} to {}",
+                name, from, to
+            ),
+            TopicChange::TopicMismatch { name, reason } => {
+                write!(f, "topic `{}` mismatch - {}", name, reason)
+            }
+        }
+    }
+}
+
+/// Partitions, replication factor and the declared configs of an existing topic
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TopicState {
+    pub(crate) partitions: i32,
+    pub(crate) replication: i32,
+    pub(crate) configs: HashMap<String, String>,
+}
+
+/// Declares the main, retry and dead-letter topics of each `TopicDefinition`.
+pub struct KafkaTopology<'tp> {
+    admin: Arc<AdminClient<DefaultClientContext>>,
+    topics: Vec<&'tp TopicDefinition>,
+    increase_partitions: bool,
+    timeout: Duration,
+}
+
+impl<'tp> KafkaTopology<'tp> {
+    pub fn new(admin: Arc<AdminClient<DefaultClientContext>>) -> KafkaTopology<'tp> {
+        KafkaTopology {
+            admin,
+            topics: vec![],
+            increase_partitions: false,
+            timeout: DEFAULT_ADMIN_TIMEOUT,
+        }
+    }
+
+    pub fn topic(mut self, def: &'tp TopicDefinition) -> Self {
+        self.topics.push(def);
+        self
+    }
+
+    ///Existing topics with less partitions than declared are expanded by `install`
+    pub fn increase_partitions(mut self) -> Self {
+        self.increase_partitions = true;
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub async fn install(&self) -> Result<(), KafkaError> {
+        let changes = self.changes().await?;
+
+        let mut creates = vec![];
+        let mut increases = vec![];
+
+        for (def, change) in &changes {
+            match change {
+                TopicChange::CreateTopic(name) => creates.push(new_topic(name, def)),
+                TopicChange::IncreasePartitions { name, to, .. } => {
+                    increases.push(NewPartitions::new(name, *to as usize))
+                }
+                TopicChange::TopicMismatch { .. } => {
+                    warn!(change = change.to_string(), "topic mismatch");
+                }
+            }
+        }
+
+        let opts = self.admin_options();
+
+        if !creates.is_empty() {
+            let results = match self.admin.create_topics(creates.iter(), &opts).await {
+                Err(err) => {
+                    error!(error = err.to_string(), "failure to create topics");
+                    Err(KafkaError::CreateTopicError(err.to_string()))
+                }
+                Ok(r) => Ok(r),
+            }?;
+
+            for result in results {
+                match result {
+                    Ok(name) => debug!("topic created: {}", name),
+                    Err((name, RDKafkaErrorCode::TopicAlreadyExists)) => {
+                        debug!("topic already created: {}", name)
+                    }
+                    Err((name, code)) => {
+                        error!(
+                            error = code.to_string(),
+                            topic = name,
+                            "failure to create topic"
+                        );
+                        return Err(KafkaError::CreateTopicError(name));
+                    }
+                }
+            }
+        }
+
+        if !increases.is_empty() {
+            let results = match self.admin.create_partitions(increases.iter(), &opts).await {
+                Err(err) => {
+                    error!(error = err.to_string(), "failure to increase partitions");
+                    Err(KafkaError::IncreasePartitionsError(err.to_string()))
+                }
+                Ok(r) => Ok(r),
+            }?;
+
+            for result in results {
+                match result {
+                    Ok(name) => debug!("topic partitions increased: {}", name),
+                    Err((name, code)) => {
+                        error!(
+                            error = code.to_string(),
+                            topic = name,
+                            "failure to increase partitions"
+                        );
+                        return Err(KafkaError::IncreasePartitionsError(name));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Dry-run of `install`
+    pub async fn diff(&self) -> Result<Vec<TopicChange>, KafkaError> {
+        Ok(self
+            .changes()
+            .await?
+            .into_iter()
+            .map(|(_, change)| change)
+            .collect())
+    }
+
+    /// Fails with `KafkaError::TopologyMismatchError` when `diff` reports any change.
+    pub async fn verify(&self) -> Result<(), KafkaError> {
+        let changes = self.diff().await?;
+
+        if changes.is_empty() {
+            debug!("topology verified");
+            return Ok(());
+        }
+
+        for change in &changes {
+            error!(change = change.to_string(), "topology mismatch");
+        }
+
+        Err(KafkaError::TopologyMismatchError(
+            changes
+                .iter()
+                .map(|c| c.to_string())
+                .collect::<Vec<String>>()
+                .join("; "),
+        ))
+    }
+
+    async fn changes(&self) -> Result<Vec<(&'tp TopicDefinition, TopicChange)>, KafkaError> {
+        // listing every topic avoids the broker auto-creating the ones being checked
+        let existing: HashMap<String, (i32, i32)> =
+            match self.admin.inner().fetch_metadata(None, self.timeout) {
+                Err(err) => {
+                    error!(error = err.to_string(), "failure to fetch metadata");
+                    Err(KafkaError::MetadataError)
+                }
+                Ok(metadata) => Ok(metadata
+                    .topics()
+                    .iter()
+                    .map(|t| {
+                        let replication = t
+                            .partitions()
+                            .first()
+                            .map_or(0, |p| p.replicas().len() as i32);
+                        (
+                            t.name().to_owned(),
+                            (t.partitions().len() as i32, replication),
+                        )
+                    })
+                    .collect()),
+            }?;
+
+        let mut changes = vec![];
+
+        for def in self.topics.clone() {
+            for name in def.declarations() {
+                let Some((partitions, replication)) = existing.get(&name).copied() else {
+                    changes.push((def, TopicChange::CreateTopic(name)));
+                    continue;
+                };
+
+                let state = TopicState {
+                    partitions,
+                    replication,
+                    configs: self.describe(&name, def).await?,
+                };
+
+                for change in compare(&name, def, &state, self.increase_partitions) {
+                    changes.push((def, change));
+                }
+            }
+        }
+
+        Ok(changes)
+    }
+
+    async fn describe(
+        &self,
+        name: &str,
+        def: &TopicDefinition,
+    ) -> Result<HashMap<String, String>, KafkaError> {
+        if def.configs.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let results = match self
+            .admin
+            .describe_configs(&[ResourceSpecifier::Topic(name)], &self.admin_options())
+            .await
+        {
+            Err(err) => {
+                error!(
+                    error = err.to_string(),
+                    topic = name,
+                    "failure to describe topic"
+                );
+                Err(KafkaError::DescribeTopicError(name.to_owned()))
+            }
+            Ok(r) => Ok(r),
+        }?;
+
+        let resource = match results.into_iter().next() {
+            Some(Ok(resource)) => Ok(resource),
+            _ => {
+                error!(topic = name, "topic configs not found");
+                Err(KafkaError::DescribeTopicError(name.to_owned()))
+            }
+        }?;
+
+        Ok(def
+            .configs
+            .keys()
+            .filter_map(|key| {
+                resource
+                    .get(key)
+                    .and_then(|entry| entry.value.clone())
+                    .map(|value| (key.clone(), value))
+            })
+            .collect())
+    }
+
+    fn admin_options(&self) -> AdminOptions {
+        AdminOptions::new().operation_timeout(Some(self.timeout))
+    }
+}
+
+fn new_topic<'d>(name: &'d str, def: &'d TopicDefinition) -> NewTopic<'d> {
+    let mut topic = NewTopic::new(
+        name,
+        def.partitions.unwrap_or(-1),
+        TopicReplication::Fixed(def.replication.unwrap_or(-1)),
+    );
+
+    for (key, value) in &def.configs {
+        topic = topic.set(key, value);
+    }
+
+    topic
+}
+
+pub(crate) fn compare(
+    name: &str,
+    def: &TopicDefinition,
+    state: &TopicState,
+    increase_partitions: bool,
+) -> Vec<TopicChange> {
+    let mut changes = vec![];
+    let mut reasons = vec![];
+
+    if let Some(partitions) = def.partitions {
+        if state.partitions < partitions && increase_partitions {
+            changes.push(TopicChange::IncreasePartitions {
+                name: name.to_owned(),
+                from: state.partitions,
+                to: partitions,
+            });
+        } else if state.partitions != partitions {
+            reasons.push(format!(
+                "partitions {} declared {}",
+                state.partitions, partitions
+            ));
+        }
+    }
+
+    if let Some(replication) = def.replication {
+        if state.replication != replication {
+            reasons.push(format!(
+                "replication factor {} declared {}",
+                state.replication, replication
+            ));
+        }
+    }
+
+    for (key, value) in &def.configs {
+        match state.configs.get(key) {
+            Some(current) if current == value => {}
+            Some(current) => reasons.push(format!("{} `{}` declared `{}`", key, current, value)),
+            None => reasons.push(format!("{} not found", key)),
+        }
+    }
+
+    if !reasons.is_empty() {
+        changes.push(TopicChange::TopicMismatch {
+            name: name.to_owned(),
+            reason: reasons.join(", "),
+        });
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::topic::TOPIC_CONFIG_RETENTION_MS;
+
+    #[test]
+    fn should_report_partition_and_config_mismatch() {
+        let def = TopicDefinition::new("orders")
+            .partitions(6)
+            .replication(3)
+            .retention(Duration::from_secs(60));
+
+        let state = TopicState {
+            partitions: 3,
+            replication: 3,
+            configs: HashMap::from([(TOPIC_CONFIG_RETENTION_MS.to_owned(), "60000".to_owned())]),
+        };
+
+        assert_eq!(
+            compare("orders", &def, &state, true),
+            vec![TopicChange::IncreasePartitions {
+                name: "orders".to_owned(),
+                from: 3,
+                to: 6
+            }]
+        );
+        assert_eq!(
+            compare("orders", &def, &state, false),
+            vec![TopicChange::TopicMismatch {
+                name: "orders".to_owned(),
+                reason: "partitions 3 declared 6".to_owned()
+            }]
+        );
+        assert_eq!(
+            compare(
+                "orders",
+                &def,
+                &TopicState {
+                    replication: 1,
+                    ..state.clone()
+                },
+                true
+            ),
+            vec![
+                TopicChange::IncreasePartitions {
+                    name: "orders".to_owned(),
+                    from: 3,
+                    to: 6
+                },
+                TopicChange::TopicMismatch {
+                    name: "orders".to_owned(),
+                    reason: "replication factor 1 declared 3".to_owned()
+                }
+            ]
+        );
+        assert_eq!(
+            compare(
+                "orders",
+                &def,
+                &TopicState {
+                    partitions: 6,
+                    ..state
+                },
+                false
+            ),
+            vec![]
+        );
+    }
+}
@@ -28,4 +28,22 @@ pub enum MessagingError {
 
     #[error("failure to publish message")]
     PublisherError,
+
+    #[error("message was rejected by the broker")]
+    PublisherNackError,
+
+    #[error("message was returned by the broker `{0}`")]
+    PublisherReturnedError(String),
+
+    #[error("timeout waiting for the broker confirmation")]
+    PublisherTimeoutError,
+
+    #[error("failure on transaction `{0}`")]
+    TransactionError(String),
+
+    #[error("schema registry failure `{0}`")]
+    SchemaRegistryError(String),
+
+    #[error("schema is incompatible with the registered versions `{0}`")]
+    IncompatibleSchemaError(String),
 }
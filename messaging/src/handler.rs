@@ -12,6 +12,8 @@ pub struct ConsumerMessage {
     pub msg_type: String,
     pub data: Box<[u8]>,
     pub headers: Option<HashMap<String, String>>,
+    ///Position of the message in its log, e.g. the RabbitMQ stream offset
+    pub offset: Option<i64>,
 }
 
 impl ConsumerMessage {
@@ -29,8 +31,14 @@ impl ConsumerMessage {
             msg_type: msg_type.into(),
             data: data.into(),
             headers,
+            offset: None,
         }
     }
+
+    pub fn with_offset(mut self, offset: i64) -> Self {
+        self.offset = Some(offset);
+        self
+    }
 }
 
 #[cfg_attr(feature = "mocks", automock)]
@@ -38,3 +46,16 @@ impl ConsumerMessage {
 pub trait ConsumerHandler: Send + Sync {
     async fn exec(&self, ctx: &Context, msg: &ConsumerMessage) -> Result<(), MessagingError>;
 }
+
+/// Handler receiving the records in batches. Returning `Err` fails the whole batch, so
+/// it is consumed again. Records that failed on their own are returned with their index
+/// in `msgs` and follow the retry flow of the broker, the others are acknowledged.
+#[cfg_attr(feature = "mocks", automock)]
+#[async_trait]
+pub trait BatchConsumerHandler: Send + Sync {
+    async fn exec(
+        &self,
+        ctx: &Context,
+        msgs: &[ConsumerMessage],
+    ) -> Result<Vec<(usize, MessagingError)>, MessagingError>;
+}
@@ -1,7 +1,7 @@
 use crate::errors::MessagingError;
 use async_trait::async_trait;
 use opentelemetry::Context;
-use std::collections::HashMap;
+use std::{collections::HashMap, time::Duration};
 
 #[cfg(feature = "mocks")]
 use mockall::*;
@@ -33,6 +33,24 @@ impl Into<String> for HeaderValues {
     }
 }
 
+///Broker message properties, unset values fall back to the publisher defaults
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MessageProperties {
+    ///Default: random uuid
+    pub message_id: Option<String>,
+    pub correlation_id: Option<String>,
+    pub reply_to: Option<String>,
+    ///Default: application/json
+    pub content_type: Option<String>,
+    pub app_id: Option<String>,
+    ///Unix timestamp in seconds. Default: now
+    pub timestamp: Option<u64>,
+    pub ttl: Option<Duration>,
+    pub priority: Option<u8>,
+    ///Default: false, messages are persisted by the broker
+    pub transient: bool,
+}
+
 #[derive(Clone)]
 pub struct PublishMessage {
     pub from: String,
@@ -41,6 +59,7 @@ pub struct PublishMessage {
     pub msg_type: String,
     pub data: Box<[u8]>,
     pub headers: Option<HashMap<String, HeaderValues>>,
+    pub properties: MessageProperties,
 }
 
 impl PublishMessage {
@@ -62,8 +81,57 @@ impl PublishMessage {
             msg_type: msg_type.into(),
             data: data.into(),
             headers,
+            properties: MessageProperties::default(),
         }
     }
+
+    ///Fixed id used by consumers to deduplicate the message
+    pub fn with_message_id(mut self, id: impl Into<String>) -> Self {
+        self.properties.message_id = Some(id.into());
+        self
+    }
+
+    pub fn with_correlation_id(mut self, id: impl Into<String>) -> Self {
+        self.properties.correlation_id = Some(id.into());
+        self
+    }
+
+    pub fn with_reply_to(mut self, reply_to: impl Into<String>) -> Self {
+        self.properties.reply_to = Some(reply_to.into());
+        self
+    }
+
+    pub fn with_content_type(mut self, content_type: impl Into<String>) -> Self {
+        self.properties.content_type = Some(content_type.into());
+        self
+    }
+
+    pub fn with_app_id(mut self, app_id: impl Into<String>) -> Self {
+        self.properties.app_id = Some(app_id.into());
+        self
+    }
+
+    pub fn with_timestamp(mut self, timestamp: u64) -> Self {
+        self.properties.timestamp = Some(timestamp);
+        self
+    }
+
+    ///Message expires if not consumed within `ttl`
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.properties.ttl = Some(ttl);
+        self
+    }
+
+    pub fn with_priority(mut self, priority: u8) -> Self {
+        self.properties.priority = Some(priority);
+        self
+    }
+
+    ///The broker may drop the message on restart
+    pub fn transient(mut self) -> Self {
+        self.properties.transient = true;
+        self
+    }
 }
 
 #[cfg_attr(feature = "mocks", automock)]
@@ -1,8 +1,11 @@
-use crate::errors::MQTTError;
-use configs::{Configs, DynamicConfigs, MQTTBrokerKind, MQTTConfigs};
+use crate::{
+    connection::{connection_events, ConnectionEvents, ConnectionState, ConnectionStateListener},
+    errors::{reason_code, MQTTError},
+};
+use configs::{Configs, DynamicConfigs, MQTTBrokerKind, MQTTConfigs, MQTTProtocolVersion};
 use paho_mqtt::{
-    AsyncClient, AsyncReceiver, ConnectOptions, ConnectOptionsBuilder, CreateOptions,
-    CreateOptionsBuilder, Message, SslOptions, SslOptionsBuilder, SslVersion,
+    AsyncClient, AsyncReceiver, ConnectOptionsBuilder, CreateOptionsBuilder, Message, Properties,
+    PropertyCode, SslOptions, SslOptionsBuilder, SslVersion, MQTT_VERSION_3_1_1, MQTT_VERSION_5,
 };
 use std::{sync::Arc, time::Duration};
 use tracing::error;
@@ -15,9 +18,13 @@ pub enum ConnectionKind {
     AWSIoTCore,
 }
 
+pub const DEFAULT_RECONNECT_MIN_INTERVAL: Duration = Duration::from_secs(1);
+pub const DEFAULT_RECONNECT_MAX_INTERVAL: Duration = Duration::from_secs(60);
+
 pub struct MQTTClient {
-    crate_opts: CreateOptions,
-    connection_opts: ConnectOptions,
+    crate_opts: CreateOptionsBuilder,
+    connection_opts: ConnectOptionsBuilder,
+    events: ConnectionEvents,
 }
 
 impl MQTTClient {
@@ -35,13 +42,27 @@ impl MQTTClient {
         return MQTTClient {
             crate_opts,
             connection_opts,
+            events: ConnectionEvents::default(),
         };
     }
 
+    /// Backoff of the automatic reconnect, doubled after each failed attempt up to `max`
+    pub fn reconnect_interval(mut self, min: Duration, max: Duration) -> Self {
+        self.connection_opts.automatic_reconnect(min, max);
+        self
+    }
+
+    pub fn state_listener(self, listener: Arc<dyn ConnectionStateListener>) -> Self {
+        self.events.listen(listener);
+        self
+    }
+
     pub async fn connect(
         self,
     ) -> Result<(Arc<AsyncClient>, AsyncReceiver<Option<Message>>), MQTTError> {
-        let mut client = match AsyncClient::new(self.crate_opts) {
+        let crate_opts = self.crate_opts.user_data(Box::new(self.events)).finalize();
+
+        let mut client = match AsyncClient::new(crate_opts) {
             Err(err) => {
                 error!(error = err.to_string(), "error to create mqtt client");
                 Err(MQTTError::ConnectionError {})
@@ -51,17 +72,37 @@ impl MQTTClient {
 
         let stream = client.get_stream(2048);
 
-        match client.connect(self.connection_opts.clone()).await {
-            Err(err) => {
-                error!(error = err.to_string(), "error to create mqtt client");
-                Err(MQTTError::ConnectionError {})
-            }
+        client.set_connected_callback(|c| notify(c, ConnectionState::Connected));
+        client.set_connection_lost_callback(|c| notify(c, ConnectionState::ConnectionLost));
+        client.set_disconnected_callback(|c, _, _| notify(c, ConnectionState::Disconnected));
+
+        match client.connect(self.connection_opts.finalize()).await {
+            Err(err) => match reason_code(&err) {
+                Some(code) => {
+                    error!(
+                        error = err.to_string(),
+                        reason_code = code.to_string(),
+                        "mqtt connection refused by the broker"
+                    );
+                    Err(MQTTError::ReasonCodeError(code.to_string()))
+                }
+                None => {
+                    error!(error = err.to_string(), "error to create mqtt client");
+                    Err(MQTTError::ConnectionError {})
+                }
+            },
             _ => Ok((Arc::new(client), stream)),
         }
     }
 }
 
-fn default_crate_opts<T>(cfgs: &Configs<T>) -> CreateOptions
+fn notify(client: &AsyncClient, state: ConnectionState) {
+    if let Some(events) = connection_events(client) {
+        events.notify(client, state);
+    }
+}
+
+fn default_crate_opts<T>(cfgs: &Configs<T>) -> CreateOptionsBuilder
 where
     T: DynamicConfigs,
 {
@@ -71,10 +112,49 @@ where
             cfgs.mqtt.transport, cfgs.mqtt.host, cfgs.mqtt.port
         ))
         .client_id(&cfgs.app.name)
-        .finalize()
+        .mqtt_version(match cfgs.mqtt.protocol_version {
+            MQTTProtocolVersion::V5 => MQTT_VERSION_5,
+            MQTTProtocolVersion::V311 => MQTT_VERSION_3_1_1,
+        })
+}
+
+/// MQTT v5 replaces the clean session by the clean start and accepts the connect properties
+fn connection_opts_builder(cfgs: &MQTTConfigs, clean: bool) -> ConnectOptionsBuilder {
+    if cfgs.protocol_version == MQTTProtocolVersion::V311 {
+        let mut builder = ConnectOptionsBuilder::new();
+        builder
+            .keep_alive_interval(Duration::from_secs(60))
+            .automatic_reconnect(
+                DEFAULT_RECONNECT_MIN_INTERVAL,
+                DEFAULT_RECONNECT_MAX_INTERVAL,
+            )
+            .clean_session(clean);
+        return builder;
+    }
+
+    let mut builder = ConnectOptionsBuilder::new_v5();
+    builder
+        .keep_alive_interval(Duration::from_secs(60))
+        .automatic_reconnect(
+            DEFAULT_RECONNECT_MIN_INTERVAL,
+            DEFAULT_RECONNECT_MAX_INTERVAL,
+        )
+        .clean_start(clean);
+
+    if cfgs.topic_alias_maximum > 0 {
+        let mut props = Properties::new();
+        match props.push_u16(PropertyCode::TopicAliasMaximum, cfgs.topic_alias_maximum) {
+            Err(err) => error!(error = err.to_string(), "invalid topic alias maximum"),
+            _ => {
+                builder.properties(props);
+            }
+        }
+    }
+
+    builder
 }
 
-fn password_connection_opts(cfgs: &MQTTConfigs) -> ConnectOptions {
+fn password_connection_opts(cfgs: &MQTTConfigs) -> ConnectOptionsBuilder {
     let mut ssl_options = SslOptions::default();
 
     if !cfgs.root_ca_path.is_empty() {
@@ -85,16 +165,15 @@ fn password_connection_opts(cfgs: &MQTTConfigs) -> ConnectOptions {
             .finalize();
     }
 
-    ConnectOptionsBuilder::new()
-        .keep_alive_interval(Duration::from_secs(60))
-        .clean_session(true)
+    let mut builder = connection_opts_builder(cfgs, true);
+    builder
         .user_name(&cfgs.user)
         .password(&cfgs.password)
-        .ssl_options(ssl_options)
-        .finalize()
+        .ssl_options(ssl_options);
+    builder
 }
 
-fn aws_iot_core_connection_opts(cfgs: &MQTTConfigs) -> ConnectOptions {
+fn aws_iot_core_connection_opts(cfgs: &MQTTConfigs) -> ConnectOptionsBuilder {
     let mut ssl_options = SslOptions::default();
 
     if !cfgs.root_ca_path.is_empty()
@@ -155,9 +234,7 @@ fn aws_iot_core_connection_opts(cfgs: &MQTTConfigs) -> ConnectOptions {
             .finalize();
     }
 
-    ConnectOptionsBuilder::new()
-        .keep_alive_interval(Duration::from_secs(60))
-        .clean_session(false)
-        .ssl_options(ssl_options)
-        .finalize()
+    let mut builder = connection_opts_builder(cfgs, false);
+    builder.ssl_options(ssl_options);
+    builder
 }
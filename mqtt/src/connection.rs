@@ -0,0 +1,91 @@
+use paho_mqtt::AsyncClient;
+use std::sync::{Arc, RwLock};
+use tracing::{debug, warn};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    ///Connected for the first time or again after losing the connection
+    Connected,
+    ///The network connection dropped, the client reconnects with backoff
+    ConnectionLost,
+    ///The broker closed the connection with a MQTT v5 DISCONNECT
+    Disconnected,
+}
+
+/// Connection state changes of a client. The callbacks run in the MQTT client thread,
+/// so they must return quickly.
+pub trait ConnectionStateListener: Send + Sync {
+    fn on_state_change(&self, client: &AsyncClient, state: ConnectionState);
+}
+
+/// Listeners of a client created by `MQTTClient::connect`, kept as the client user data
+#[derive(Default)]
+pub struct ConnectionEvents {
+    listeners: RwLock<Vec<Arc<dyn ConnectionStateListener>>>,
+}
+
+impl ConnectionEvents {
+    pub fn listen(&self, listener: Arc<dyn ConnectionStateListener>) {
+        if let Ok(mut listeners) = self.listeners.write() {
+            listeners.push(listener);
+        }
+    }
+
+    pub(crate) fn notify(&self, client: &AsyncClient, state: ConnectionState) {
+        match state {
+            ConnectionState::Connected => debug!("mqtt connected"),
+            _ => warn!(state = format!("{:?}", state), "mqtt connection lost"),
+        }
+
+        let listeners = match self.listeners.read() {
+            Ok(listeners) => listeners.clone(),
+            Err(_) => return,
+        };
+
+        for listener in listeners {
+            listener.on_state_change(client, state);
+        }
+    }
+}
+
+/// Connection events of the client, `None` when it was not created by `MQTTClient`
+pub fn connection_events(client: &AsyncClient) -> Option<&ConnectionEvents> {
+    client
+        .user_data()
+        .and_then(|data| data.downcast_ref::<ConnectionEvents>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use paho_mqtt::CreateOptions;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct RecordingListener {
+        states: Mutex<Vec<ConnectionState>>,
+    }
+
+    impl ConnectionStateListener for RecordingListener {
+        fn on_state_change(&self, _client: &AsyncClient, state: ConnectionState) {
+            self.states.lock().unwrap().push(state);
+        }
+    }
+
+    #[test]
+    fn test_notify_listeners() {
+        let client = AsyncClient::new(CreateOptions::default()).unwrap();
+        let listener = Arc::new(RecordingListener::default());
+
+        let events = ConnectionEvents::default();
+        events.listen(listener.clone());
+        events.notify(&client, ConnectionState::ConnectionLost);
+        events.notify(&client, ConnectionState::Connected);
+
+        assert_eq!(
+            *listener.states.lock().unwrap(),
+            vec![ConnectionState::ConnectionLost, ConnectionState::Connected]
+        );
+        assert!(connection_events(&client).is_none());
+    }
+}
@@ -14,12 +14,22 @@ use paho_mqtt::{AsyncClient, AsyncReceiver, Message, TopicFilter};
 use std::{borrow::Cow, sync::Arc};
 use tracing::{debug, error, warn};
 
+use crate::{
+    client::{DEFAULT_RECONNECT_MAX_INTERVAL, DEFAULT_RECONNECT_MIN_INTERVAL},
+    connection::connection_events,
+    errors::reason_code,
+    properties::consumer_headers,
+};
+
+const SHARED_SUBSCRIPTION_PREFIX: &str = "$share/";
+
 pub struct MQTTDispatcher {
     conn: Arc<AsyncClient>,
     stream: AsyncReceiver<Option<Message>>,
     tracer: BoxedTracer,
     topics: Vec<String>,
     handlers: Vec<Arc<dyn ConsumerHandler>>,
+    shared_group: Option<String>,
 }
 
 impl MQTTDispatcher {
@@ -30,8 +40,17 @@ impl MQTTDispatcher {
             tracer: global::tracer("mqtt-consumer"),
             topics: vec![],
             handlers: vec![],
+            shared_group: None,
         }
     }
+
+    /// Subscribes the registered topics as `$share/{group}/{topic}`, so the messages are
+    /// load-balanced between the consumers of the group. Topics registered with the
+    /// `$share/` prefix are subscribed as they are.
+    pub fn shared(mut self, group: &str) -> Self {
+        self.shared_group = Some(group.to_owned());
+        self
+    }
 }
 
 #[async_trait]
@@ -53,8 +72,8 @@ impl Dispatcher for MQTTDispatcher {
     }
 
     async fn consume_blocking(&self) -> Result<(), MessagingError> {
-        for topic in self.topics.clone() {
-            self.conn.subscribe(topic, 2);
+        for topic in &self.topics {
+            self.subscribe(topic).await?;
         }
 
         let mut cloned_stream = self.stream.clone();
@@ -65,7 +84,10 @@ impl Dispatcher for MQTTDispatcher {
                     Err(e) => error!(error = e.to_string(), "failure to consume msg"),
                     _ => {}
                 },
-                _ => {}
+                None => {
+                    self.wait_reconnection().await;
+                    self.resubscribe().await;
+                }
             }
         }
 
@@ -74,6 +96,89 @@ impl Dispatcher for MQTTDispatcher {
 }
 
 impl MQTTDispatcher {
+    /// The stream yields `None` when the connection drops. Clients created by `MQTTClient`
+    /// reconnect automatically, otherwise the reconnect is requested with backoff.
+    async fn wait_reconnection(&self) {
+        let mut backoff = DEFAULT_RECONNECT_MIN_INTERVAL;
+
+        while !self.conn.is_connected() {
+            warn!(
+                backoff = format!("{:?}", backoff),
+                "waiting mqtt reconnection"
+            );
+            tokio::time::sleep(backoff).await;
+
+            if !self.conn.is_connected() && connection_events(&self.conn).is_none() {
+                if let Err(err) = self.conn.reconnect().await {
+                    debug!(error = err.to_string(), "failure to reconnect");
+                }
+            }
+
+            backoff = (backoff * 2).min(DEFAULT_RECONNECT_MAX_INTERVAL);
+        }
+
+        debug!("mqtt reconnected, resubscribing topics");
+    }
+
+    /// Subscribes the registered topics again, retrying each one with backoff until the
+    /// broker accepts it.
+    async fn resubscribe(&self) {
+        for topic in &self.topics {
+            let mut backoff = DEFAULT_RECONNECT_MIN_INTERVAL;
+
+            while self.subscribe(topic).await.is_err() {
+                warn!(
+                    topic = topic,
+                    backoff = format!("{:?}", backoff),
+                    "retrying mqtt subscription"
+                );
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(DEFAULT_RECONNECT_MAX_INTERVAL);
+
+                self.wait_reconnection().await;
+            }
+        }
+    }
+
+    async fn subscribe(&self, topic: &str) -> Result<(), MessagingError> {
+        let subscription = match &self.shared_group {
+            Some(group) if !topic.starts_with(SHARED_SUBSCRIPTION_PREFIX) => {
+                format!("{}{}/{}", SHARED_SUBSCRIPTION_PREFIX, group, topic)
+            }
+            _ => topic.to_owned(),
+        };
+
+        let reason = match self.conn.subscribe(subscription.clone(), 2).await {
+            Err(err) => {
+                error!(
+                    error = err.to_string(),
+                    topic = subscription,
+                    "failure to subscribe"
+                );
+                reason_code(&err)
+                    .map(|code| code.to_string())
+                    .unwrap_or(err.to_string())
+            }
+            Ok(rsp) if rsp.reason_code().is_err() => {
+                error!(
+                    reason_code = rsp.reason_code().to_string(),
+                    topic = subscription,
+                    "subscription refused by the broker"
+                );
+                rsp.reason_code().to_string()
+            }
+            Ok(_) => {
+                debug!(topic = subscription, "subscribed");
+                return Ok(());
+            }
+        };
+
+        Err(MessagingError::ConsumerError(format!(
+            "{} - {}",
+            subscription, reason
+        )))
+    }
+
     async fn consume(&self, ctx: &Context, msg: &Message) -> Result<(), MessagingError> {
         let handler_idx = self.get_handler_index(ctx, msg.topic())?;
 
@@ -89,7 +194,12 @@ impl MQTTDispatcher {
 
         let handler = self.handlers.get(handler_idx).unwrap();
 
-        let msg = ConsumerMessage::new(msg.topic(), "", msg.payload(), None);
+        let msg = ConsumerMessage::new(
+            msg.topic(),
+            "",
+            msg.payload(),
+            consumer_headers(msg.properties()),
+        );
 
         return match handler.exec(&ctx, &msg).await {
             Ok(_) => {
@@ -126,7 +236,7 @@ impl MQTTDispatcher {
         for handler_topic_index in 0..self.topics.len() {
             let handler_topic = self.topics[handler_topic_index].clone();
 
-            match TopicFilter::new(&handler_topic) {
+            match TopicFilter::new(topic_filter(&handler_topic)) {
                 Ok(filter) => {
                     if filter.is_match(received_topic) {
                         p = handler_topic_index;
@@ -160,6 +270,14 @@ impl MQTTDispatcher {
     }
 }
 
+/// Topic filter of a subscription, without the `$share/{group}/` prefix of the shared ones
+fn topic_filter(topic: &str) -> &str {
+    match topic.strip_prefix(SHARED_SUBSCRIPTION_PREFIX) {
+        Some(shared) => shared.split_once('/').map_or(topic, |(_, filter)| filter),
+        None => topic,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -265,6 +383,30 @@ mod tests {
         assert!(res.is_err());
     }
 
+    #[tokio::test]
+    async fn test_consume_with_shared_subscription() {
+        let mut client = AsyncClient::new(CreateOptions::default()).unwrap();
+        let stream = client.get_stream(2048);
+
+        let mut handler = MockConsumerHandler::new();
+        handler.expect_exec().return_once(move |_, _| Ok(()));
+
+        let dispatcher = MQTTDispatcher::new(Arc::new(client), stream).register(
+            &DispatcherDefinition {
+                name: "$share/consumers/devices/+/telemetry".to_owned(),
+                msg_type: String::new(),
+            },
+            Arc::new(handler),
+        );
+
+        let msg = Message::new("devices/1/telemetry", vec![], 0);
+
+        let res = dispatcher.consume(&Context::new(), &msg).await;
+        assert!(res.is_ok());
+        assert_eq!(topic_filter("$share/consumers/devices/#"), "devices/#");
+        assert_eq!(topic_filter("devices/#"), "devices/#");
+    }
+
     #[tokio::test]
     async fn test_consume_with_unregistered_consumer() {
         let mut client = AsyncClient::new(CreateOptions::default()).unwrap();
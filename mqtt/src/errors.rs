@@ -1,3 +1,4 @@
+use paho_mqtt::ReasonCode;
 use thiserror::Error;
 
 #[derive(Error, Debug, PartialEq, Eq, Clone)]
@@ -35,6 +36,9 @@ pub enum MQTTError {
     #[error("mqtt failure to subscribe in a topic")]
     SubscribeError,
 
+    #[error("mqtt request refused by the broker: `{0}`")]
+    ReasonCodeError(String),
+
     #[error("mqtt dispatcher error")]
     DispatcherError,
 
@@ -44,3 +48,11 @@ pub enum MQTTError {
     #[error("error to deserialization collector log message - CollectorLogMessageDeserializationError: `{0}`")]
     CollectorLogMessageDeserializationError(String),
 }
+
+/// Reason code sent by a MQTT v5 broker when refusing a request
+pub fn reason_code(err: &paho_mqtt::Error) -> Option<ReasonCode> {
+    match err {
+        paho_mqtt::Error::ReasonCode(code) => Some(*code),
+        _ => None,
+    }
+}
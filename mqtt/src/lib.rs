@@ -1,5 +1,7 @@
 pub mod client;
+pub mod connection;
 pub mod dispatcher;
 pub mod errors;
 pub mod payload;
+pub mod properties;
 pub mod publisher;
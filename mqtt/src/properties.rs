@@ -0,0 +1,154 @@
+use messaging::publisher::{HeaderValues, PublishMessage};
+use paho_mqtt::{Properties, PropertyCode};
+use std::collections::HashMap;
+use tracing::warn;
+
+/// Int or LongInt, QoS of the published message. Default: 0
+pub const QOS_HEADER_KEY: &str = "qos";
+
+/// Uint or LongUint, alias of the topic in the connection
+pub const TOPIC_ALIAS_HEADER_KEY: &str = "mqtt-topic-alias";
+
+/// Content type of a received MQTT v5 message
+pub const CONTENT_TYPE_HEADER_KEY: &str = "mqtt-content-type";
+
+/// Response topic of a received MQTT v5 message
+pub const RESPONSE_TOPIC_HEADER_KEY: &str = "mqtt-response-topic";
+
+/// Correlation data of a received MQTT v5 message
+pub const CORRELATION_DATA_HEADER_KEY: &str = "mqtt-correlation-data";
+
+/// MQTT v5 properties of a message: the headers are sent as user properties and the
+/// content type, reply to, correlation id and ttl as their MQTT counterparts.
+pub fn publish_properties(msg: &PublishMessage) -> Properties {
+    let mut props = Properties::new();
+
+    for (key, value) in msg.headers.clone().unwrap_or_default() {
+        let result = match key.as_str() {
+            QOS_HEADER_KEY => continue,
+            TOPIC_ALIAS_HEADER_KEY => match value {
+                HeaderValues::Uint(alias) => props.push_u16(PropertyCode::TopicAlias, alias as u16),
+                HeaderValues::LongUint(alias) => {
+                    props.push_u16(PropertyCode::TopicAlias, alias as u16)
+                }
+                _ => continue,
+            },
+            _ => {
+                let value: String = value.into();
+                props.push_string_pair(PropertyCode::UserProperty, &key, &value)
+            }
+        };
+
+        if let Err(err) = result {
+            warn!(
+                error = err.to_string(),
+                header = key,
+                "ignoring invalid property"
+            );
+        }
+    }
+
+    let properties = &msg.properties;
+
+    let mut results = vec![];
+    if let Some(content_type) = &properties.content_type {
+        results.push(props.push_string(PropertyCode::ContentType, content_type));
+    }
+    if let Some(reply_to) = &properties.reply_to {
+        results.push(props.push_string(PropertyCode::ResponseTopic, reply_to));
+    }
+    if let Some(correlation_id) = &properties.correlation_id {
+        results.push(props.push_binary(PropertyCode::CorrelationData, correlation_id.as_bytes()));
+    }
+    if let Some(ttl) = properties.ttl {
+        results.push(props.push_u32(
+            PropertyCode::MessageExpiryInterval,
+            ttl.as_secs().max(1) as u32,
+        ));
+    }
+
+    for err in results.into_iter().filter_map(|r| r.err()) {
+        warn!(error = err.to_string(), "ignoring invalid property");
+    }
+
+    props
+}
+
+/// Headers of a received message: the user properties plus the content type, response
+/// topic and correlation data.
+pub fn consumer_headers(props: &Properties) -> Option<HashMap<String, String>> {
+    let mut headers: HashMap<String, String> = props.user_iter().collect();
+
+    if let Some(content_type) = props.get_string(PropertyCode::ContentType) {
+        headers.insert(CONTENT_TYPE_HEADER_KEY.to_owned(), content_type);
+    }
+    if let Some(response_topic) = props.get_string(PropertyCode::ResponseTopic) {
+        headers.insert(RESPONSE_TOPIC_HEADER_KEY.to_owned(), response_topic);
+    }
+    if let Some(correlation) = props.get_binary(PropertyCode::CorrelationData) {
+        headers.insert(
+            CORRELATION_DATA_HEADER_KEY.to_owned(),
+            String::from_utf8_lossy(&correlation).into_owned(),
+        );
+    }
+
+    if headers.is_empty() {
+        return None;
+    }
+
+    Some(headers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use messaging::publisher::MessageProperties;
+    use std::time::Duration;
+
+    #[test]
+    fn test_properties_round_trip() {
+        let msg = PublishMessage {
+            from: String::new(),
+            to: "devices/1/cmd".to_owned(),
+            key: String::new(),
+            msg_type: String::new(),
+            data: Box::new([]),
+            headers: Some(HashMap::from([
+                (
+                    "tenant".to_owned(),
+                    HeaderValues::ShortString("acme".to_owned()),
+                ),
+                (QOS_HEADER_KEY.to_owned(), HeaderValues::Int(1)),
+                (TOPIC_ALIAS_HEADER_KEY.to_owned(), HeaderValues::Uint(3)),
+            ])),
+            properties: MessageProperties {
+                content_type: Some("application/json".to_owned()),
+                reply_to: Some("devices/1/reply".to_owned()),
+                correlation_id: Some("request-1".to_owned()),
+                ttl: Some(Duration::from_secs(30)),
+                ..Default::default()
+            },
+        };
+
+        let props = publish_properties(&msg);
+        assert_eq!(props.get_int(PropertyCode::TopicAlias), Some(3));
+        assert_eq!(props.get_int(PropertyCode::MessageExpiryInterval), Some(30));
+
+        let headers = consumer_headers(&props).unwrap();
+        assert_eq!(headers.len(), 4);
+        assert_eq!(headers.get("tenant"), Some(&"acme".to_owned()));
+        assert_eq!(
+            headers.get(CONTENT_TYPE_HEADER_KEY),
+            Some(&"application/json".to_owned())
+        );
+        assert_eq!(
+            headers.get(RESPONSE_TOPIC_HEADER_KEY),
+            Some(&"devices/1/reply".to_owned())
+        );
+        assert_eq!(
+            headers.get(CORRELATION_DATA_HEADER_KEY),
+            Some(&"request-1".to_owned())
+        );
+        assert_eq!(consumer_headers(&Properties::new()), None);
+    }
+}
@@ -1,3 +1,8 @@
+use crate::{
+    connection::{connection_events, ConnectionState, ConnectionStateListener},
+    errors::reason_code,
+    properties::{publish_properties, QOS_HEADER_KEY},
+};
 use async_trait::async_trait;
 use messaging::{
     errors::MessagingError,
@@ -7,29 +12,152 @@ use opentelemetry::{
     trace::{Status, TraceContextExt},
     Context,
 };
-use paho_mqtt::{AsyncClient, Message};
-use std::{borrow::Cow, sync::Arc};
-use tracing::error;
+use paho_mqtt::{AsyncClient, DeliveryToken, Message, MessageBuilder, MQTT_VERSION_5};
+use std::{
+    borrow::Cow,
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    thread,
+};
+use tracing::{debug, error, warn};
 
 pub struct MQTTPublisher {
     conn: Arc<AsyncClient>,
+    buffer: Option<Arc<OfflineBuffer>>,
 }
 
 impl MQTTPublisher {
     pub fn new(conn: Arc<AsyncClient>) -> Self {
-        Self { conn }
+        Self { conn, buffer: None }
+    }
+
+    /// Keeps up to `capacity` messages published while disconnected, publishing fails
+    /// once it is full. They are sent once reconnected, or on the next publish when the
+    /// client was not created by `MQTTClient`. A message whose delivery fails is queued
+    /// again as soon as it fails, and counts against `capacity` until delivered.
+    pub fn offline_buffer(mut self, capacity: usize) -> Self {
+        let buffer = Arc::new(OfflineBuffer::new(capacity));
+
+        match connection_events(&self.conn) {
+            Some(events) => events.listen(buffer.clone()),
+            None => warn!("mqtt client without connection events, buffer flushed on publish"),
+        }
+
+        self.buffer = Some(buffer);
+        self
+    }
+}
+
+#[derive(Default)]
+struct BufferedMessages {
+    ///Waiting for the connection
+    queued: VecDeque<Message>,
+    ///Flushed, waiting for the delivery to complete
+    in_flight: usize,
+}
+
+struct OfflineBuffer {
+    capacity: usize,
+    msgs: Arc<Mutex<BufferedMessages>>,
+}
+
+impl OfflineBuffer {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            msgs: Arc::new(Mutex::new(BufferedMessages::default())),
+        }
+    }
+
+    ///Returns `false` when the buffer is full
+    fn push(&self, msg: Message) -> bool {
+        let Ok(mut msgs) = self.msgs.lock() else {
+            return false;
+        };
+
+        if msgs.queued.len() + msgs.in_flight >= self.capacity {
+            warn!(
+                topic = msg.topic(),
+                "mqtt offline buffer full, message rejected"
+            );
+            return false;
+        }
+
+        msgs.queued.push_back(msg);
+        true
+    }
+
+    ///Publishes the queued messages when connected, their deliveries are watched by a
+    ///dedicated thread
+    fn flush(&self, client: &AsyncClient) {
+        let tokens = {
+            let Ok(mut msgs) = self.msgs.lock() else {
+                return;
+            };
+
+            if msgs.queued.is_empty() || !client.is_connected() {
+                return;
+            }
+
+            debug!(count = msgs.queued.len(), "publishing mqtt offline buffer");
+
+            let tokens: Vec<DeliveryToken> = msgs
+                .queued
+                .drain(..)
+                .map(|msg| client.publish(msg))
+                .collect();
+            msgs.in_flight += tokens.len();
+            tokens
+        };
+
+        let msgs = self.msgs.clone();
+        thread::spawn(move || watch_deliveries(&msgs, tokens));
+    }
+}
+
+///Waits for the deliveries in order, the failed messages are queued again ahead of the
+///messages buffered in the meantime
+fn watch_deliveries(msgs: &Mutex<BufferedMessages>, tokens: Vec<DeliveryToken>) {
+    let mut requeued = 0;
+
+    for token in tokens {
+        let msg = token.message().clone();
+        let delivered = token.wait();
+
+        let Ok(mut msgs) = msgs.lock() else {
+            return;
+        };
+        msgs.in_flight = msgs.in_flight.saturating_sub(1);
+
+        if let Err(err) = delivered {
+            warn!(
+                error = err.to_string(),
+                topic = msg.topic(),
+                "mqtt buffered message not delivered, queued again"
+            );
+            msgs.queued.insert(requeued, msg);
+            requeued += 1;
+        }
+    }
+}
+
+impl ConnectionStateListener for OfflineBuffer {
+    fn on_state_change(&self, client: &AsyncClient, _state: ConnectionState) {
+        self.flush(client);
     }
 }
 
 #[async_trait]
 impl Publisher for MQTTPublisher {
+    /// With MQTT v5 connections the headers and the message properties are sent as
+    /// MQTT properties, see `properties::publish_properties`.
     async fn publish(&self, ctx: &Context, infos: &PublishMessage) -> Result<(), MessagingError> {
         let span = ctx.span();
 
         let mut qos: i32 = 0;
 
         if let Some(headers) = &infos.headers {
-            if let Some(custom_qos) = headers.get("qos") {
+            if let Some(custom_qos) = headers.get(QOS_HEADER_KEY) {
                 if let HeaderValues::Int(custom) = custom_qos {
                     qos = custom.to_owned() as i32;
                 }
@@ -40,13 +168,40 @@ impl Publisher for MQTTPublisher {
             }
         }
 
-        match self
-            .conn
-            .publish(Message::new(infos.to.clone(), infos.data.clone(), qos))
-            .await
-        {
+        let mut msg = MessageBuilder::new()
+            .topic(infos.to.clone())
+            .payload(infos.data.clone())
+            .qos(qos);
+
+        if self.conn.mqtt_version() >= MQTT_VERSION_5 {
+            msg = msg.properties(publish_properties(infos));
+        }
+
+        let msg = msg.finalize();
+
+        if let Some(buffer) = &self.buffer {
+            buffer.flush(&self.conn);
+
+            if !self.conn.is_connected() {
+                if !buffer.push(msg) {
+                    span.set_status(Status::Error {
+                        description: Cow::from("offline buffer full"),
+                    });
+                    return Err(MessagingError::PublisherError);
+                }
+
+                span.set_status(Status::Ok);
+                return Ok(());
+            }
+        }
+
+        match self.conn.publish(msg).await {
             Err(err) => {
-                error!(error = err.to_string(), "error to publish message");
+                error!(
+                    error = err.to_string(),
+                    reason_code = reason_code(&err).map(|code| code.to_string()),
+                    "error to publish message"
+                );
 
                 span.record_error(&err);
                 span.set_status(Status::Error {
@@ -62,3 +217,45 @@ impl Publisher for MQTTPublisher {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offline_buffer_rejects_when_full() {
+        let buffer = OfflineBuffer::new(2);
+
+        assert!(buffer.push(Message::new("a", "1", 0)));
+        assert!(buffer.push(Message::new("a", "2", 0)));
+        assert!(!buffer.push(Message::new("a", "3", 0)));
+
+        let msgs = buffer.msgs.lock().unwrap();
+        assert_eq!(msgs.queued.len(), 2);
+        assert_eq!(msgs.queued[0].payload_str(), "1");
+        assert_eq!(msgs.queued[1].payload_str(), "2");
+    }
+
+    #[test]
+    fn test_offline_buffer_requeues_failed_deliveries() {
+        let buffer = OfflineBuffer::new(3);
+        assert!(buffer.push(Message::new("a", "3", 0)));
+        buffer.msgs.lock().unwrap().in_flight = 2;
+
+        let tokens = vec![
+            DeliveryToken::from_error(Message::new("a", "1", 0), -3),
+            DeliveryToken::from_error(Message::new("a", "2", 0), -3),
+        ];
+        watch_deliveries(&buffer.msgs, tokens);
+
+        let msgs = buffer.msgs.lock().unwrap();
+        assert_eq!(msgs.in_flight, 0);
+        assert_eq!(
+            msgs.queued
+                .iter()
+                .map(|m| m.payload_str().into_owned())
+                .collect::<Vec<_>>(),
+            vec!["1", "2", "3"]
+        );
+    }
+}
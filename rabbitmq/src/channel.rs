@@ -1,7 +1,11 @@
-use crate::errors::AmqpError;
-use configs::{Configs, DynamicConfigs};
-use lapin::{types::LongString, Channel, Connection, ConnectionProperties};
-use std::sync::Arc;
+use crate::{errors::AmqpError, pool::ChannelPool, tls};
+use configs::{Configs, DynamicConfigs, RabbitMQAuthMechanism};
+use lapin::{
+    types::LongString,
+    uri::{AMQPUri, SASLMechanism},
+    Channel, Connection, ConnectionProperties,
+};
+use std::{str::FromStr, sync::Arc};
 use tracing::{debug, error};
 
 pub async fn new_amqp_channel<T>(
@@ -14,8 +18,21 @@ where
     let options = ConnectionProperties::default()
         .with_connection_name(LongString::from(cfg.app.name.clone()));
 
-    let uri = &cfg.rabbitmq_uri();
-    let conn = match Connection::connect(uri, options).await {
+    let uri = amqp_uri(cfg)?;
+
+    let connected = if cfg.rabbitmq.tls {
+        let connector = tls::new_tls_connector(&cfg.rabbitmq)?;
+        let server_name = match cfg.rabbitmq.tls_server_name.is_empty() {
+            true => cfg.rabbitmq.host.clone(),
+            false => cfg.rabbitmq.tls_server_name.clone(),
+        };
+
+        Connection::connector(uri, tls::connector(connector, server_name), options).await
+    } else {
+        Connection::connect_uri(uri, options).await
+    };
+
+    let conn = match connected {
         Ok(c) => Ok(c),
         Err(err) => {
             error!(error = err.to_string(), "failure to connect");
@@ -36,3 +53,57 @@ where
         }
     }
 }
+
+fn amqp_uri<T>(cfg: &Configs<T>) -> Result<AMQPUri, AmqpError>
+where
+    T: DynamicConfigs,
+{
+    let mut uri = match AMQPUri::from_str(&cfg.rabbitmq_uri()) {
+        Err(err) => {
+            error!(error = err, "invalid amqp uri");
+            Err(AmqpError::ConnectionError {})
+        }
+        Ok(u) => Ok(u),
+    }?;
+
+    uri.query.heartbeat = Some(cfg.rabbitmq.heartbeat);
+    uri.query.connection_timeout = Some(cfg.rabbitmq.connection_timeout);
+    if cfg.rabbitmq.auth_mechanism == RabbitMQAuthMechanism::External {
+        uri.query.auth_mechanism = Some(SASLMechanism::External);
+    }
+
+    Ok(uri)
+}
+
+/// Publisher channel pool sized by `RabbitMQConfigs::channel_pool_size`,
+/// the channels are opened on the given connection apart from the consumer channel.
+pub fn new_channel_pool<T>(cfg: &Configs<T>, conn: Arc<Connection>) -> ChannelPool
+where
+    T: DynamicConfigs,
+{
+    ChannelPool::new(conn, cfg.rabbitmq.channel_pool_size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use configs::Empty;
+    use lapin::uri::AMQPScheme;
+
+    #[test]
+    fn should_configure_amqp_uri() {
+        let mut cfg = Configs::<Empty>::default();
+        cfg.rabbitmq.tls = true;
+        cfg.rabbitmq.port = 5671;
+        cfg.rabbitmq.heartbeat = 30;
+        cfg.rabbitmq.auth_mechanism = RabbitMQAuthMechanism::External;
+
+        let uri = amqp_uri(&cfg).unwrap();
+
+        assert_eq!(uri.scheme, AMQPScheme::AMQPS);
+        assert_eq!(uri.authority.port, 5671);
+        assert_eq!(uri.query.heartbeat, Some(30));
+        assert_eq!(uri.query.connection_timeout, Some(30000));
+        assert_eq!(uri.query.auth_mechanism, Some(SASLMechanism::External));
+    }
+}
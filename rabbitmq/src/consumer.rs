@@ -1,9 +1,14 @@
-use crate::{dispatcher::RabbitMQDispatcherDefinition, errors::AmqpError, otel};
+use crate::{
+    dispatcher::{RabbitMQDispatcherDefinition, AMQP_HEADERS_STREAM_OFFSET},
+    errors::AmqpError,
+    otel,
+    queue::QueueKind,
+};
 use lapin::{
     message::Delivery,
     options::{BasicAckOptions, BasicNackOptions, BasicPublishOptions},
     protocol::basic::AMQPProperties,
-    types::FieldTable,
+    types::{AMQPValue, FieldTable, LongInt, ShortString},
     Channel,
 };
 use messaging::handler::ConsumerMessage;
@@ -16,6 +21,7 @@ use tracing::{debug, error, warn};
 
 pub const AMQP_HEADERS_X_DEATH: &str = "x-death";
 pub const AMQP_HEADERS_COUNT: &str = "count";
+pub const AMQP_HEADERS_RETRY_COUNT: &str = "x-retry-count";
 
 pub(crate) async fn consume<'c>(
     tracer: &BoxedTracer,
@@ -60,12 +66,15 @@ pub(crate) async fn consume<'c>(
         return Err(AmqpError::InternalError {});
     };
 
-    let msg = ConsumerMessage::new(
+    let mut msg = ConsumerMessage::new(
         &dispatcher_def.queue_def.name,
         &msg_type,
         &delivery.data,
         None,
     );
+    if let Some(offset) = extract_stream_offset(&delivery.properties) {
+        msg = msg.with_offset(offset);
+    }
 
     let result = dispatcher_def.handler.exec(&ctx, &msg).await;
     if result.is_ok() {
@@ -90,8 +99,91 @@ pub(crate) async fn consume<'c>(
         }
     }
 
+    //streams are append-only logs, nack does not remove the msg so only ack is meaningful
+    if dispatcher_def.queue_def.kind == QueueKind::Stream {
+        error!(
+            trace.id = traces::trace_id(&ctx),
+            span.id = traces::span_id(&ctx),
+            "error whiling handling stream msg, skipping offset {:?}",
+            msg.offset
+        );
+        span.set_status(Status::Error {
+            description: Cow::from("error to handle stream msg"),
+        });
+        match delivery.ack(BasicAckOptions { multiple: false }).await {
+            Err(e) => {
+                span.record_error(&e);
+                return Err(AmqpError::AckMessageError {});
+            }
+            _ => return Ok(()),
+        }
+    }
+
+    //send msg to the backoff tier of the current attempt, the tier queue dead-letters it back when the ttl expires
+    let backoff = &dispatcher_def.queue_def.retry_backoff;
+    let attempt = extract_retry_attempt(&delivery.properties);
+    if (attempt as usize) < backoff.len() {
+        let tier = attempt as usize + 1;
+        warn!(
+            trace.id = traces::trace_id(&ctx),
+            span.id = traces::span_id(&ctx),
+            "error whiling handling msg, sending to retry tier {}",
+            tier
+        );
+
+        let mut headers = match delivery.properties.headers() {
+            Some(val) => val.to_owned(),
+            None => FieldTable::default(),
+        };
+        headers.insert(
+            ShortString::from(AMQP_HEADERS_RETRY_COUNT),
+            AMQPValue::LongInt(LongInt::from(tier as i32)),
+        );
+
+        match channel
+            .basic_publish(
+                "",
+                &dispatcher_def.queue_def.retry_tier_name(tier),
+                BasicPublishOptions::default(),
+                &delivery.data,
+                delivery.properties.clone().with_headers(headers),
+            )
+            .await
+        {
+            Err(e) => {
+                error!(
+                    trace.id = traces::trace_id(&ctx),
+                    span.id = traces::span_id(&ctx),
+                    "error whiling sending to retry tier"
+                );
+                span.record_error(&e);
+                span.set_status(Status::Error {
+                    description: Cow::from("error to requeuing msg"),
+                });
+                return Err(AmqpError::RequeuingMessageError {});
+            }
+            _ => match delivery.ack(BasicAckOptions { multiple: false }).await {
+                Err(e) => {
+                    error!(
+                        trace.id = traces::trace_id(&ctx),
+                        span.id = traces::span_id(&ctx),
+                        "error whiling ack msg to default queue"
+                    );
+                    span.record_error(&e);
+                    span.set_status(Status::Error {
+                        description: Cow::from("error to ack msg"),
+                    });
+                    return Err(AmqpError::AckMessageError {});
+                }
+                _ => return Ok(()),
+            },
+        }
+    }
+
     //ack msg and remove from queue if handler failure and there are no fallback configured or send to dlq
-    if dispatcher_def.queue_def.retry_name.is_none() {
+    if dispatcher_def.queue_def.retry_name.is_none()
+        && (backoff.is_empty() || dispatcher_def.queue_def.dlq_name.is_none())
+    {
         match delivery
             .nack(BasicNackOptions {
                 multiple: false,
@@ -116,7 +208,7 @@ pub(crate) async fn consume<'c>(
     }
 
     //send msg to retry when handler failure and the retry count Dont active the max of the retries configured
-    if count < dispatcher_def.queue_def.retries.unwrap() as i64 {
+    if count < dispatcher_def.queue_def.retries.unwrap_or_default() as i64 {
         warn!(
             trace.id = traces::trace_id(&ctx),
             span.id = traces::span_id(&ctx),
@@ -194,7 +286,7 @@ pub(crate) async fn consume<'c>(
     }
 }
 
-fn extract_header_properties(props: &AMQPProperties) -> (String, i64) {
+pub(crate) fn extract_header_properties(props: &AMQPProperties) -> (String, i64) {
     let headers = match props.headers() {
         Some(val) => val.to_owned(),
         None => FieldTable::default(),
@@ -227,3 +319,25 @@ fn extract_header_properties(props: &AMQPProperties) -> (String, i64) {
 
     (msg_type, count)
 }
+
+fn extract_retry_attempt(props: &AMQPProperties) -> i64 {
+    match props.headers() {
+        Some(headers) => match headers.inner().get(AMQP_HEADERS_RETRY_COUNT) {
+            Some(AMQPValue::LongInt(val)) => *val as i64,
+            Some(AMQPValue::LongLongInt(val)) => *val,
+            _ => 0,
+        },
+        None => 0,
+    }
+}
+
+fn extract_stream_offset(props: &AMQPProperties) -> Option<i64> {
+    match props.headers() {
+        Some(headers) => match headers.inner().get(AMQP_HEADERS_STREAM_OFFSET) {
+            Some(AMQPValue::LongLongInt(val)) => Some(*val),
+            Some(AMQPValue::LongInt(val)) => Some(*val as i64),
+            _ => None,
+        },
+        None => None,
+    }
+}
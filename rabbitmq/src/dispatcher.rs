@@ -1,15 +1,29 @@
-use crate::{consumer::consume, queue::QueueDefinition};
+use crate::{
+    consumer::consume,
+    queue::{QueueDefinition, QueueKind},
+};
 use async_trait::async_trait;
 use futures_util::{future::join_all, StreamExt};
-use lapin::{options::BasicConsumeOptions, types::FieldTable, Channel};
+use lapin::{
+    options::{BasicConsumeOptions, BasicQosOptions},
+    types::{AMQPValue, FieldTable, LongInt, ShortString},
+    Channel, Consumer,
+};
 use messaging::{
     dispatcher::{Dispatcher, DispatcherDefinition},
     errors::MessagingError,
     handler::ConsumerHandler,
 };
 use opentelemetry::global;
-use std::{collections::HashMap, sync::Arc};
-use tracing::error;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+use tracing::{debug, error};
+use uuid::Uuid;
+
+pub const AMQP_HEADERS_CONSUMER_PRIORITY: &str = "x-priority";
+pub const AMQP_HEADERS_STREAM_OFFSET: &str = "x-stream-offset";
 
 #[derive(Clone)]
 pub struct RabbitMQDispatcherDefinition {
@@ -61,27 +75,7 @@ impl RabbitMQDispatcher {
         let key = self.dispatchers_def.keys().next().unwrap();
         let def = self.dispatchers_def.get(key).unwrap();
 
-        let mut consumer = match self
-            .channel
-            .basic_consume(
-                &def.queue_def.name,
-                key,
-                BasicConsumeOptions {
-                    no_local: false,
-                    no_ack: false,
-                    exclusive: false,
-                    nowait: false,
-                },
-                FieldTable::default(),
-            )
-            .await
-        {
-            Err(err) => {
-                error!(error = err.to_string(), "error to create the consumer");
-                Err(MessagingError::CreatingConsumerError)
-            }
-            Ok(c) => Ok(c),
-        }?;
+        let mut consumer = self.create_consumer(key, def, false).await?;
 
         let defs = self.dispatchers_def.clone();
         let channel = self.channel.clone();
@@ -124,27 +118,8 @@ impl RabbitMQDispatcher {
         let mut spawns = vec![];
 
         for (msg_type, def) in &self.dispatchers_def {
-            let mut consumer = match self
-                .channel
-                .basic_consume(
-                    &def.queue_def.name,
-                    &msg_type,
-                    BasicConsumeOptions {
-                        no_local: false,
-                        no_ack: false,
-                        exclusive: false,
-                        nowait: false,
-                    },
-                    FieldTable::default(),
-                )
-                .await
-            {
-                Err(err) => {
-                    error!(error = err.to_string(), "failure to create the consumer");
-                    Err(MessagingError::CreatingConsumerError)
-                }
-                Ok(c) => Ok(c),
-            }?;
+            let shared = self.consumers_of(&def.queue_def.name) > 1;
+            let mut consumer = self.create_consumer(msg_type, def, shared).await?;
 
             let defs = self.dispatchers_def.clone();
             let channel = self.channel.clone();
@@ -186,4 +161,128 @@ impl RabbitMQDispatcher {
 
         Ok(())
     }
+
+    /// Number of msg_types registered for `queue`, each one gets its own consumer
+    fn consumers_of(&self, queue: &str) -> usize {
+        self.dispatchers_def
+            .values()
+            .filter(|def| def.queue_def.name == queue)
+            .count()
+    }
+
+    /// `shared` when other consumers of the same queue are started on the channel
+    async fn create_consumer(
+        &self,
+        msg_type: &str,
+        def: &RabbitMQDispatcherDefinition,
+        shared: bool,
+    ) -> Result<Consumer, MessagingError> {
+        let queue_def = &def.queue_def;
+
+        if shared && queue_def.consumer_exclusive {
+            error!(
+                queue = queue_def.name,
+                "exclusive consumers require a single msg_type per queue"
+            );
+            return Err(MessagingError::CreatingConsumerError);
+        }
+
+        if queue_def.kind == QueueKind::Stream && queue_def.prefetch_count.is_none() {
+            error!(
+                queue = queue_def.name,
+                "stream consumers require a prefetch"
+            );
+            return Err(MessagingError::CreatingConsumerError);
+        }
+
+        // qos applies to the consumers started later on the shared channel, so it is
+        // set for every consumer and reset to unlimited when not configured
+        let prefetch = queue_def.prefetch_count.unwrap_or(0);
+        debug!(
+            queue = queue_def.name,
+            prefetch = prefetch,
+            "configuring consumer qos"
+        );
+
+        match self
+            .channel
+            .basic_qos(prefetch, BasicQosOptions { global: false })
+            .await
+        {
+            Err(err) => {
+                error!(error = err.to_string(), "failure to configure consumer qos");
+                Err(MessagingError::CreatingConsumerError)
+            }
+            _ => Ok(()),
+        }?;
+
+        let mut args = BTreeMap::new();
+        if let Some(priority) = queue_def.consumer_priority {
+            args.insert(
+                ShortString::from(AMQP_HEADERS_CONSUMER_PRIORITY),
+                AMQPValue::LongInt(LongInt::from(priority)),
+            );
+        }
+
+        if let Some(offset) = &queue_def.stream_offset {
+            args.insert(
+                ShortString::from(AMQP_HEADERS_STREAM_OFFSET),
+                AMQPValue::from(offset),
+            );
+        }
+
+        let tag = consumer_tag(queue_def, msg_type, shared);
+
+        match self
+            .channel
+            .basic_consume(
+                &queue_def.name,
+                &tag,
+                BasicConsumeOptions {
+                    no_local: false,
+                    no_ack: false,
+                    exclusive: queue_def.consumer_exclusive,
+                    nowait: false,
+                },
+                FieldTable::from(args),
+            )
+            .await
+        {
+            Err(err) => {
+                error!(error = err.to_string(), "failure to create the consumer");
+                Err(MessagingError::CreatingConsumerError)
+            }
+            Ok(c) => Ok(c),
+        }
+    }
+}
+
+/// Consumer tags must be unique per channel, so a configured tag shared by several
+/// msg_types is suffixed with the msg_type
+fn consumer_tag(queue_def: &QueueDefinition, msg_type: &str, shared: bool) -> String {
+    match &queue_def.consumer_tag {
+        Some(tag) if shared => format!("{}-{}", tag, msg_type),
+        Some(tag) => tag.clone(),
+        None => format!("{}-{}-{}", queue_def.name, msg_type, Uuid::new_v4()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_create_unique_consumer_tags() {
+        let def = QueueDefinition::new("orders");
+        let created = consumer_tag(&def, "order-created", false);
+        assert!(created.starts_with("orders-order-created-"));
+        assert_ne!(created, consumer_tag(&def, "order-created", false));
+
+        let def = def.consumer_tag("billing");
+        assert_eq!(consumer_tag(&def, "order-created", false), "billing");
+        assert_ne!(
+            consumer_tag(&def, "order-created", true),
+            consumer_tag(&def, "order-paid", true)
+        );
+    }
 }
@@ -0,0 +1,534 @@
+use crate::{
+    consumer::{extract_header_properties, AMQP_HEADERS_RETRY_COUNT, AMQP_HEADERS_X_DEATH},
+    errors::AmqpError,
+    publisher::DEFAULT_CONFIRM_TIMEOUT,
+    queue::QueueDefinition,
+};
+use lapin::{
+    message::BasicGetMessage,
+    options::{
+        BasicAckOptions, BasicGetOptions, BasicNackOptions, BasicPublishOptions,
+        ConfirmSelectOptions, QueueDeclareOptions, QueuePurgeOptions,
+    },
+    publisher_confirm::Confirmation,
+    types::{AMQPValue, FieldTable},
+    Channel, Connection,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, sync::Arc};
+use tokio::sync::Mutex;
+use tracing::{debug, error, warn};
+
+pub const AMQP_HEADERS_X_FIRST_DEATH_PREFIX: &str = "x-first-death-";
+pub const AMQP_HEADERS_X_LAST_DEATH_PREFIX: &str = "x-last-death-";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DlqMessage {
+    pub msg_type: String,
+    ///Number of times the message was dead-lettered by the broker
+    pub deaths: i64,
+    pub headers: HashMap<String, String>,
+    ///Base64 encoded when serialized
+    #[serde(with = "base64_data")]
+    pub data: Vec<u8>,
+}
+
+mod base64_data {
+    use base64::{engine::general_purpose, Engine};
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&general_purpose::STANDARD.encode(data))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        general_purpose::STANDARD
+            .decode(encoded)
+            .map_err(D::Error::custom)
+    }
+}
+
+///Selects the dlq messages to requeue, an empty filter matches every message
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DlqFilter {
+    pub msg_type: Option<String>,
+    pub header: Option<String>,
+    pub header_value: Option<String>,
+}
+
+impl DlqFilter {
+    pub fn msg_type(mut self, msg_type: &str) -> Self {
+        self.msg_type = Some(msg_type.to_owned());
+        self
+    }
+
+    pub fn header(mut self, key: &str, value: &str) -> Self {
+        self.header = Some(key.to_owned());
+        self.header_value = Some(value.to_owned());
+        self
+    }
+
+    pub(crate) fn matches(&self, msg: &DlqMessage) -> bool {
+        if let Some(msg_type) = &self.msg_type {
+            if &msg.msg_type != msg_type {
+                return false;
+            }
+        }
+
+        if let Some(key) = &self.header {
+            return match msg.headers.get(key) {
+                Some(value) => match &self.header_value {
+                    Some(expected) => value == expected,
+                    None => true,
+                },
+                None => false,
+            };
+        }
+
+        true
+    }
+}
+
+/// Inspects and drains the `-dlq` queues created by `QueueDefinition::with_dlq`.
+/// Messages are read with basic.get on a dedicated channel in confirm mode, opened on `conn`.
+pub struct DlqManager {
+    conn: Arc<Connection>,
+    ///Serializes the operations, a multiple nack covers every delivery of the channel.
+    ///Recreated when the broker closed it.
+    channel: Mutex<Option<Arc<Channel>>>,
+}
+
+impl DlqManager {
+    pub fn new(conn: Arc<Connection>) -> Arc<DlqManager> {
+        Arc::new(DlqManager {
+            conn,
+            channel: Mutex::new(None),
+        })
+    }
+
+    /// Checked on a short-lived channel, a missing dlq closes the channel it was checked on.
+    pub async fn count(&self, def: &QueueDefinition) -> Result<u32, AmqpError> {
+        let dlq = dlq_name(def)?;
+        let channel = self.create_channel().await?;
+
+        let count = match channel
+            .queue_declare(
+                dlq,
+                QueueDeclareOptions {
+                    passive: true,
+                    ..QueueDeclareOptions::default()
+                },
+                FieldTable::default(),
+            )
+            .await
+        {
+            Err(err) => {
+                error!(error = err.to_string(), queue = dlq, "failure to count dlq");
+                return Err(AmqpError::DlqReadError(dlq.to_owned()));
+            }
+            Ok(queue) => queue.message_count(),
+        };
+
+        if let Err(err) = channel.close(200, "OK").await {
+            warn!(error = err.to_string(), "failure to close the channel");
+        }
+
+        Ok(count)
+    }
+
+    ///Reads up to `limit` messages and returns them to the dlq untouched
+    pub async fn peek(
+        &self,
+        def: &QueueDefinition,
+        limit: usize,
+    ) -> Result<Vec<DlqMessage>, AmqpError> {
+        let dlq = dlq_name(def)?;
+        self.count(def).await?;
+
+        let mut slot = self.channel.lock().await;
+        let channel = self.channel(&mut slot).await?;
+
+        let mut messages = vec![];
+        let mut last_tag = None;
+
+        while messages.len() < limit {
+            let Some(msg) = get(&channel, dlq).await? else {
+                break;
+            };
+
+            last_tag = Some(msg.delivery.delivery_tag);
+            messages.push(dlq_message(&msg));
+        }
+
+        if let Some(tag) = last_tag {
+            nack(&channel, tag, true).await?;
+        }
+
+        Ok(messages)
+    }
+
+    pub async fn requeue_all(&self, def: &QueueDefinition) -> Result<u32, AmqpError> {
+        self.requeue(def, &DlqFilter::default()).await
+    }
+
+    /// Publishes the matching messages back to the origin queue with the retry counter reset.
+    /// Each message is removed from the dlq only after the broker confirmed the publish.
+    /// Messages that do not match are kept in the dlq.
+    pub async fn requeue(
+        &self,
+        def: &QueueDefinition,
+        filter: &DlqFilter,
+    ) -> Result<u32, AmqpError> {
+        let dlq = dlq_name(def)?;
+
+        // only the messages present when the requeue starts are visited
+        let total = self.count(def).await?;
+
+        let mut slot = self.channel.lock().await;
+        let channel = self.channel(&mut slot).await?;
+
+        let mut requeued = 0;
+        let mut kept = vec![];
+
+        for _ in 0..total {
+            let msg = match get(&channel, dlq).await {
+                Err(err) => {
+                    nack_all(&channel, &kept).await?;
+                    return Err(err);
+                }
+                Ok(None) => break,
+                Ok(Some(msg)) => msg,
+            };
+
+            let tag = msg.delivery.delivery_tag;
+
+            if !filter.matches(&dlq_message(&msg)) {
+                kept.push(tag);
+                continue;
+            }
+
+            if let Err(err) = republish(&channel, def, &msg).await {
+                kept.push(tag);
+                nack_all(&channel, &kept).await?;
+                return Err(err);
+            }
+
+            match channel
+                .basic_ack(tag, BasicAckOptions { multiple: false })
+                .await
+            {
+                Err(err) => {
+                    error!(
+                        error = err.to_string(),
+                        queue = dlq,
+                        "failure to ack dlq msg"
+                    );
+                    Err(AmqpError::AckMessageError)
+                }
+                _ => Ok(()),
+            }?;
+
+            requeued += 1;
+        }
+
+        nack_all(&channel, &kept).await?;
+
+        debug!(queue = dlq, requeued = requeued, "dlq messages requeued");
+
+        Ok(requeued)
+    }
+
+    pub async fn purge(&self, def: &QueueDefinition) -> Result<u32, AmqpError> {
+        let dlq = dlq_name(def)?;
+        self.count(def).await?;
+
+        let mut slot = self.channel.lock().await;
+        let channel = self.channel(&mut slot).await?;
+
+        match channel
+            .queue_purge(dlq, QueuePurgeOptions { nowait: false })
+            .await
+        {
+            Err(err) => {
+                error!(error = err.to_string(), queue = dlq, "failure to purge dlq");
+                Err(AmqpError::PurgeQueueError(dlq.to_owned()))
+            }
+            Ok(count) => {
+                warn!(queue = dlq, purged = count, "dlq purged");
+                Ok(count)
+            }
+        }
+    }
+
+    async fn channel(&self, slot: &mut Option<Arc<Channel>>) -> Result<Arc<Channel>, AmqpError> {
+        if let Some(channel) = slot.as_ref() {
+            if channel.status().connected() {
+                return Ok(channel.clone());
+            }
+
+            warn!("dlq channel is closed, recycling");
+        }
+
+        let channel = Arc::new(self.create_channel().await?);
+
+        match channel
+            .confirm_select(ConfirmSelectOptions { nowait: false })
+            .await
+        {
+            Err(err) => {
+                error!(
+                    error = err.to_string(),
+                    "failure to enable publisher confirms"
+                );
+                Err(AmqpError::ConfirmSelectError)
+            }
+            _ => Ok(()),
+        }?;
+
+        *slot = Some(channel.clone());
+
+        Ok(channel)
+    }
+
+    async fn create_channel(&self) -> Result<Channel, AmqpError> {
+        match self.conn.create_channel().await {
+            Err(err) => {
+                error!(error = err.to_string(), "error to create the channel");
+                Err(AmqpError::ChannelError {})
+            }
+            Ok(c) => Ok(c),
+        }
+    }
+}
+
+async fn get(channel: &Channel, dlq: &str) -> Result<Option<BasicGetMessage>, AmqpError> {
+    match channel
+        .basic_get(dlq, BasicGetOptions { no_ack: false })
+        .await
+    {
+        Err(err) => {
+            error!(error = err.to_string(), queue = dlq, "failure to read dlq");
+            Err(AmqpError::DlqReadError(dlq.to_owned()))
+        }
+        Ok(msg) => Ok(msg),
+    }
+}
+
+async fn republish(
+    channel: &Channel,
+    def: &QueueDefinition,
+    msg: &BasicGetMessage,
+) -> Result<(), AmqpError> {
+    let props = msg
+        .delivery
+        .properties
+        .clone()
+        .with_headers(reset_headers(msg.delivery.properties.headers()));
+
+    let confirm = match channel
+        .basic_publish(
+            "",
+            &def.name,
+            BasicPublishOptions {
+                mandatory: true,
+                immediate: false,
+            },
+            &msg.delivery.data,
+            props,
+        )
+        .await
+    {
+        Err(err) => {
+            error!(
+                error = err.to_string(),
+                queue = def.name,
+                "failure to requeue dlq msg"
+            );
+            Err(AmqpError::RequeuingMessageError)
+        }
+        Ok(c) => Ok(c),
+    }?;
+
+    match tokio::time::timeout(DEFAULT_CONFIRM_TIMEOUT, confirm).await {
+        Ok(Ok(Confirmation::Ack(None))) => Ok(()),
+        Ok(Ok(confirmation)) => {
+            error!(
+                queue = def.name,
+                confirmation = format!("{:?}", confirmation),
+                "requeued dlq msg not confirmed by the broker"
+            );
+            Err(AmqpError::RequeuingMessageError)
+        }
+        Ok(Err(err)) => {
+            error!(
+                error = err.to_string(),
+                queue = def.name,
+                "error waiting requeue confirmation"
+            );
+            Err(AmqpError::RequeuingMessageError)
+        }
+        Err(_) => {
+            error!(queue = def.name, "timeout waiting requeue confirmation");
+            Err(AmqpError::RequeuingMessageError)
+        }
+    }
+}
+
+async fn nack_all(channel: &Channel, tags: &[u64]) -> Result<(), AmqpError> {
+    for tag in tags {
+        nack(channel, *tag, false).await?;
+    }
+
+    Ok(())
+}
+
+async fn nack(channel: &Channel, tag: u64, multiple: bool) -> Result<(), AmqpError> {
+    match channel
+        .basic_nack(
+            tag,
+            BasicNackOptions {
+                multiple,
+                requeue: true,
+            },
+        )
+        .await
+    {
+        Err(err) => {
+            error!(error = err.to_string(), "failure to return msg to dlq");
+            Err(AmqpError::NackMessageError)
+        }
+        _ => Ok(()),
+    }
+}
+
+fn dlq_name(def: &QueueDefinition) -> Result<&str, AmqpError> {
+    match &def.dlq_name {
+        Some(name) => Ok(name),
+        None => Err(AmqpError::DlqNotConfiguredError(def.name.clone())),
+    }
+}
+
+fn dlq_message(msg: &BasicGetMessage) -> DlqMessage {
+    let (msg_type, deaths) = extract_header_properties(&msg.delivery.properties);
+
+    let mut headers = HashMap::new();
+    if let Some(table) = msg.delivery.properties.headers() {
+        for (key, value) in table.inner() {
+            if let Some(value) = header_to_string(value) {
+                headers.insert(key.to_string(), value);
+            }
+        }
+    }
+
+    DlqMessage {
+        msg_type,
+        deaths,
+        headers,
+        data: msg.delivery.data.clone(),
+    }
+}
+
+fn header_to_string(value: &AMQPValue) -> Option<String> {
+    match value {
+        AMQPValue::LongString(val) => Some(val.to_string()),
+        AMQPValue::ShortString(val) => Some(val.to_string()),
+        AMQPValue::Boolean(val) => Some(val.to_string()),
+        AMQPValue::ShortInt(val) => Some(val.to_string()),
+        AMQPValue::LongInt(val) => Some(val.to_string()),
+        AMQPValue::LongLongInt(val) => Some(val.to_string()),
+        AMQPValue::Timestamp(val) => Some(val.to_string()),
+        _ => None,
+    }
+}
+
+///Removes the broker and consumer retry bookkeeping so the message starts over
+pub(crate) fn reset_headers(headers: &Option<FieldTable>) -> FieldTable {
+    let mut table = FieldTable::default();
+
+    if let Some(headers) = headers {
+        for (key, value) in headers.inner() {
+            let name = key.as_str();
+            if name == AMQP_HEADERS_X_DEATH
+                || name == AMQP_HEADERS_RETRY_COUNT
+                || name.starts_with(AMQP_HEADERS_X_FIRST_DEATH_PREFIX)
+                || name.starts_with(AMQP_HEADERS_X_LAST_DEATH_PREFIX)
+            {
+                continue;
+            }
+            table.insert(key.clone(), value.clone());
+        }
+    }
+
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lapin::types::{FieldArray, LongString, ShortString};
+
+    fn message(msg_type: &str, headers: &[(&str, &str)]) -> DlqMessage {
+        DlqMessage {
+            msg_type: msg_type.to_owned(),
+            deaths: 1,
+            headers: headers
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            data: vec![],
+        }
+    }
+
+    #[test]
+    fn should_match_filters() {
+        let msg = message("order-created", &[("tenant", "acme")]);
+
+        assert!(DlqFilter::default().matches(&msg));
+        assert!(DlqFilter::default().msg_type("order-created").matches(&msg));
+        assert!(!DlqFilter::default().msg_type("order-paid").matches(&msg));
+        assert!(DlqFilter::default().header("tenant", "acme").matches(&msg));
+        assert!(!DlqFilter::default().header("tenant", "other").matches(&msg));
+        assert!(!DlqFilter::default()
+            .msg_type("order-created")
+            .header("region", "eu")
+            .matches(&msg));
+    }
+
+    #[test]
+    fn should_serialize_data_as_base64() {
+        let mut msg = message("order-created", &[]);
+        msg.data = b"{\"id\":1}".to_vec();
+
+        let json = serde_json::to_value(&msg).unwrap();
+        assert_eq!(json["data"], "eyJpZCI6MX0=");
+        assert_eq!(serde_json::from_value::<DlqMessage>(json).unwrap(), msg);
+    }
+
+    #[test]
+    fn should_reset_retry_headers() {
+        let mut headers = FieldTable::default();
+        headers.insert(
+            ShortString::from(AMQP_HEADERS_X_DEATH),
+            AMQPValue::FieldArray(FieldArray::default()),
+        );
+        headers.insert(
+            ShortString::from(AMQP_HEADERS_RETRY_COUNT),
+            AMQPValue::LongInt(3),
+        );
+        headers.insert(
+            ShortString::from("x-first-death-queue"),
+            AMQPValue::LongString(LongString::from("orders")),
+        );
+        headers.insert(
+            ShortString::from("tenant"),
+            AMQPValue::LongString(LongString::from("acme")),
+        );
+
+        let reset = reset_headers(&Some(headers));
+
+        assert_eq!(reset.inner().len(), 1);
+        assert!(reset.inner().contains_key("tenant"));
+    }
+}
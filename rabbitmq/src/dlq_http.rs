@@ -0,0 +1,126 @@
+use crate::{
+    dlq::{DlqFilter, DlqManager},
+    errors::AmqpError,
+    queue::QueueDefinition,
+};
+use actix_web::{
+    delete, get, post,
+    web::{self, Data, Path, Query, ServiceConfig},
+    HttpResponse,
+};
+use http_components::{viewmodels::HTTPError, CustomServiceConfigure};
+use serde::Deserialize;
+use serde_json::json;
+use std::{collections::HashMap, sync::Arc};
+
+pub const DEFAULT_DLQ_PEEK_LIMIT: usize = 10;
+
+struct DlqRoutesState {
+    manager: Arc<DlqManager>,
+    queues: HashMap<String, QueueDefinition>,
+}
+
+#[derive(Deserialize)]
+struct PeekQuery {
+    limit: Option<usize>,
+}
+
+/// Routes to operate the dlq of `queues` for `TinyHTTPServer::custom_configure`:
+///
+/// - GET /dlq/{queue}/count
+/// - GET /dlq/{queue}/messages?limit=10
+/// - POST /dlq/{queue}/requeue?msg_type=&header=&header_value=
+/// - DELETE /dlq/{queue}
+pub fn dlq_routes(manager: Arc<DlqManager>, queues: &[QueueDefinition]) -> CustomServiceConfigure {
+    let state = Data::new(DlqRoutesState {
+        manager,
+        queues: queues
+            .iter()
+            .filter(|q| q.dlq_name.is_some())
+            .map(|q| (q.name.clone(), q.clone()))
+            .collect(),
+    });
+
+    CustomServiceConfigure::new(move |cfg: &mut ServiceConfig| {
+        cfg.service(
+            web::scope("/dlq")
+                .app_data(state.clone())
+                .service(dlq_count_handler)
+                .service(dlq_messages_handler)
+                .service(dlq_requeue_handler)
+                .service(dlq_purge_handler),
+        );
+    })
+}
+
+#[get("/{queue}/count")]
+async fn dlq_count_handler(
+    state: Data<DlqRoutesState>,
+    queue: Path<String>,
+) -> Result<HttpResponse, HTTPError> {
+    let def = queue_def(&state, &queue)?;
+
+    match state.manager.count(def).await {
+        Ok(count) => Ok(HttpResponse::Ok().json(json!({ "queue": def.name, "count": count }))),
+        Err(err) => Err(internal_error(err)),
+    }
+}
+
+#[get("/{queue}/messages")]
+async fn dlq_messages_handler(
+    state: Data<DlqRoutesState>,
+    queue: Path<String>,
+    query: Query<PeekQuery>,
+) -> Result<HttpResponse, HTTPError> {
+    let def = queue_def(&state, &queue)?;
+
+    match state
+        .manager
+        .peek(def, query.limit.unwrap_or(DEFAULT_DLQ_PEEK_LIMIT))
+        .await
+    {
+        Ok(messages) => Ok(HttpResponse::Ok().json(messages)),
+        Err(err) => Err(internal_error(err)),
+    }
+}
+
+#[post("/{queue}/requeue")]
+async fn dlq_requeue_handler(
+    state: Data<DlqRoutesState>,
+    queue: Path<String>,
+    filter: Query<DlqFilter>,
+) -> Result<HttpResponse, HTTPError> {
+    let def = queue_def(&state, &queue)?;
+
+    match state.manager.requeue(def, &filter).await {
+        Ok(count) => Ok(HttpResponse::Ok().json(json!({ "queue": def.name, "requeued": count }))),
+        Err(err) => Err(internal_error(err)),
+    }
+}
+
+#[delete("/{queue}")]
+async fn dlq_purge_handler(
+    state: Data<DlqRoutesState>,
+    queue: Path<String>,
+) -> Result<HttpResponse, HTTPError> {
+    let def = queue_def(&state, &queue)?;
+
+    match state.manager.purge(def).await {
+        Ok(count) => Ok(HttpResponse::Ok().json(json!({ "queue": def.name, "purged": count }))),
+        Err(err) => Err(internal_error(err)),
+    }
+}
+
+fn queue_def<'s>(state: &'s DlqRoutesState, queue: &str) -> Result<&'s QueueDefinition, HTTPError> {
+    match state.queues.get(queue) {
+        Some(def) => Ok(def),
+        None => Err(HTTPError::not_found(
+            "queue not found",
+            format!("queue `{}` has no dlq managed by this service", queue),
+        )),
+    }
+}
+
+fn internal_error(err: AmqpError) -> HTTPError {
+    HTTPError::internal_server_error("dlq operation failure", err.to_string())
+}
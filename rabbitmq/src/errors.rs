@@ -8,6 +8,9 @@ pub enum AmqpError {
     #[error("failure to connect")]
     ConnectionError,
 
+    #[error("invalid tls configuration `{0}`")]
+    TlsConfigError(String),
+
     #[error("failure to create a channel")]
     ChannelError,
 
@@ -17,15 +20,48 @@ pub enum AmqpError {
     #[error("failure to declare a queue `{0}`")]
     DeclareQueueError(String),
 
+    #[error("failure to delete an exchange `{0}`")]
+    DeleteExchangeError(String),
+
+    #[error("failure to delete a queue `{0}`")]
+    DeleteQueueError(String),
+
+    #[error("failure to purge a queue `{0}`")]
+    PurgeQueueError(String),
+
+    #[error("queue `{0}` has no dlq configured")]
+    DlqNotConfiguredError(String),
+
+    #[error("failure to read dlq `{0}`")]
+    DlqReadError(String),
+
+    #[error("topology mismatch `{0}`")]
+    TopologyMismatchError(String),
+
+    #[error("invalid queue definition `{0}` - {1}")]
+    InvalidQueueDefinitionError(String, String),
+
     #[error("failure to binding exchange `{0}` to queue `{0}`")]
     BindingExchangeToQueueError(String, String),
 
+    #[error("failure to binding exchange `{0}` to exchange `{1}`")]
+    BindingExchangeToExchangeError(String, String),
+
+    #[error("failure to load topology `{0}`")]
+    LoadTopologyError(String),
+
     #[error("failure to declare consumer `{0}`")]
     BindingConsumerError(String),
 
     #[error("failure to publish")]
     PublishingError,
 
+    #[error("failure to enable publisher confirms")]
+    ConfirmSelectError,
+
+    #[error("mandatory publishing requires publisher confirms")]
+    MandatoryWithoutConfirmsError,
+
     #[error("failure to parse payload")]
     ParsePayloadError,
 
@@ -1,16 +1,19 @@
 use crate::errors::AmqpError;
 use lapin::types::{AMQPValue, LongString, ShortString};
+use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 
 pub const AMQP_HEADERS_DELAYED_EXCHANGE_TYPE: &str = "x-delayed-type";
 
-#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ExchangeKind {
     #[default]
     Direct,
     Fanout,
     Topic,
     Headers,
+    #[serde(rename = "x-delayed-message")]
     XMessageDelayed,
 }
 
@@ -110,7 +113,7 @@ impl<'ex> ExchangeDefinition<'ex> {
     }
 
     pub fn passive(mut self) -> Self {
-        self.passive = self.passive;
+        self.passive = true;
         self
     }
 
@@ -125,4 +128,47 @@ impl<'ex> ExchangeDefinition<'ex> {
     }
 }
 
-pub struct ExchangeBinding {}
+pub struct ExchangeBinding<'eb> {
+    pub(crate) source: &'eb str,
+    pub(crate) destination: &'eb str,
+    pub(crate) routing_key: &'eb str,
+    pub(crate) no_wait: bool,
+    pub(crate) params: BTreeMap<ShortString, AMQPValue>,
+}
+
+impl<'eb> ExchangeBinding<'eb> {
+    pub fn new(destination: &'eb str) -> ExchangeBinding<'eb> {
+        ExchangeBinding {
+            source: "",
+            destination,
+            routing_key: "",
+            no_wait: false,
+            params: BTreeMap::default(),
+        }
+    }
+
+    pub fn source(mut self, exchange: &'eb str) -> Self {
+        self.source = exchange;
+        self
+    }
+
+    pub fn routing_key(mut self, key: &'eb str) -> Self {
+        self.routing_key = key;
+        self
+    }
+
+    pub fn params(mut self, params: BTreeMap<ShortString, AMQPValue>) -> Self {
+        self.params = params;
+        self
+    }
+
+    pub fn param(mut self, key: ShortString, value: AMQPValue) -> Self {
+        self.params.insert(key, value);
+        self
+    }
+
+    pub fn no_wait(mut self) -> Self {
+        self.no_wait = true;
+        self
+    }
+}
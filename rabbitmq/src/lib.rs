@@ -1,10 +1,16 @@
 mod consumer;
 mod otel;
+mod tls;
 
 pub mod channel;
 pub mod dispatcher;
+pub mod dlq;
+#[cfg(feature = "http")]
+pub mod dlq_http;
 pub mod errors;
 pub mod exchange;
+pub mod pool;
 pub mod publisher;
 pub mod queue;
 pub mod topology;
+pub mod topology_spec;
@@ -0,0 +1,92 @@
+use crate::errors::AmqpError;
+use lapin::{options::ConfirmSelectOptions, Channel, Connection};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+use tokio::sync::Mutex;
+use tracing::{debug, error, warn};
+
+pub use configs::DEFAULT_CHANNEL_POOL_SIZE;
+
+/// Set of channels handed out round-robin to concurrent publishers.
+/// Channels are opened lazily and replaced when the broker closed them.
+pub struct ChannelPool {
+    conn: Arc<Connection>,
+    slots: Vec<Mutex<Option<Arc<Channel>>>>,
+    next: AtomicUsize,
+    confirms: bool,
+}
+
+impl ChannelPool {
+    pub fn new(conn: Arc<Connection>, size: usize) -> ChannelPool {
+        ChannelPool {
+            conn,
+            slots: (0..size.max(1)).map(|_| Mutex::new(None)).collect(),
+            next: AtomicUsize::new(0),
+            confirms: false,
+        }
+    }
+
+    ///Every channel of the pool is created in confirm mode
+    pub fn confirms(mut self) -> Self {
+        self.confirms = true;
+        self
+    }
+
+    pub fn size(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub(crate) fn has_confirms(&self) -> bool {
+        self.confirms
+    }
+
+    pub async fn get(&self) -> Result<Arc<Channel>, AmqpError> {
+        let idx = self.next.fetch_add(1, Ordering::Relaxed) % self.slots.len();
+        let mut slot = self.slots[idx].lock().await;
+
+        if let Some(channel) = slot.as_ref() {
+            if channel.status().connected() {
+                return Ok(channel.clone());
+            }
+
+            warn!(slot = idx, "pooled channel is closed, recycling");
+        }
+
+        let channel = Arc::new(self.create_channel().await?);
+        *slot = Some(channel.clone());
+
+        debug!(slot = idx, "pooled channel created");
+
+        Ok(channel)
+    }
+
+    async fn create_channel(&self) -> Result<Channel, AmqpError> {
+        let channel = match self.conn.create_channel().await {
+            Err(err) => {
+                error!(error = err.to_string(), "error to create the channel");
+                Err(AmqpError::ChannelError {})
+            }
+            Ok(c) => Ok(c),
+        }?;
+
+        if !self.confirms {
+            return Ok(channel);
+        }
+
+        match channel
+            .confirm_select(ConfirmSelectOptions { nowait: false })
+            .await
+        {
+            Err(err) => {
+                error!(
+                    error = err.to_string(),
+                    "failure to enable publisher confirms"
+                );
+                Err(AmqpError::ConfirmSelectError)
+            }
+            _ => Ok(channel),
+        }
+    }
+}
@@ -1,7 +1,8 @@
-use crate::otel::RabbitMQTracePropagator;
+use crate::{errors::AmqpError, otel::RabbitMQTracePropagator, pool::ChannelPool};
 use async_trait::async_trait;
 use lapin::{
-    options::BasicPublishOptions,
+    options::{BasicPublishOptions, ConfirmSelectOptions},
+    publisher_confirm::Confirmation,
     types::{
         AMQPValue, FieldTable, LongInt, LongLongInt, LongString, LongUInt, ShortInt, ShortString,
     },
@@ -9,25 +10,95 @@ use lapin::{
 };
 use messaging::{
     errors::MessagingError,
-    publisher::{HeaderValues, PublishMessage, Publisher},
+    publisher::{HeaderValues, MessageProperties, PublishMessage, Publisher},
 };
 use opentelemetry::{global, Context};
 use std::{
     collections::{BTreeMap, HashMap},
     sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
-use tracing::error;
+use tracing::{debug, error};
 use uuid::Uuid;
 
 pub const JSON_CONTENT_TYPE: &str = "application/json";
+pub const AMQP_DELIVERY_MODE_TRANSIENT: u8 = 1;
+pub const AMQP_DELIVERY_MODE_PERSISTENT: u8 = 2;
+
+///Default timeout waiting for the broker to confirm a published message
+pub const DEFAULT_CONFIRM_TIMEOUT: Duration = Duration::from_secs(5);
+
+enum PublisherChannel {
+    Single(Arc<Channel>),
+    Pool(Arc<ChannelPool>),
+}
 
 pub struct RabbitMQPublisher {
-    channel: Arc<Channel>,
+    channel: PublisherChannel,
+    confirms: bool,
+    mandatory: bool,
+    confirm_timeout: Duration,
 }
 
 impl RabbitMQPublisher {
     pub fn new(channel: Arc<Channel>) -> Arc<RabbitMQPublisher> {
-        Arc::new(RabbitMQPublisher { channel })
+        Arc::new(RabbitMQPublisher {
+            channel: PublisherChannel::Single(channel),
+            confirms: false,
+            mandatory: false,
+            confirm_timeout: DEFAULT_CONFIRM_TIMEOUT,
+        })
+    }
+
+    /// Puts the channel in confirm mode, so every publish waits up to `timeout` for the broker ack.
+    /// When `mandatory` is set, messages that can not be routed to any queue are returned by the
+    /// broker and reported as `MessagingError::PublisherReturnedError`.
+    pub async fn new_with_confirms(
+        channel: Arc<Channel>,
+        mandatory: bool,
+        timeout: Duration,
+    ) -> Result<Arc<RabbitMQPublisher>, AmqpError> {
+        match channel
+            .confirm_select(ConfirmSelectOptions { nowait: false })
+            .await
+        {
+            Err(err) => {
+                error!(
+                    error = err.to_string(),
+                    "failure to enable publisher confirms"
+                );
+                Err(AmqpError::ConfirmSelectError)
+            }
+            _ => Ok(()),
+        }?;
+
+        Ok(Arc::new(RabbitMQPublisher {
+            channel: PublisherChannel::Single(channel),
+            confirms: true,
+            mandatory,
+            confirm_timeout: timeout,
+        }))
+    }
+
+    /// Publishes through the pool channels, waiting for the broker ack only when the pool
+    /// was created with confirms. Returned messages are only reported with confirms, so
+    /// `mandatory` requires a pool with confirms.
+    pub fn new_with_pool(
+        pool: Arc<ChannelPool>,
+        mandatory: bool,
+        timeout: Duration,
+    ) -> Result<Arc<RabbitMQPublisher>, AmqpError> {
+        if mandatory && !pool.has_confirms() {
+            error!("mandatory publishing requires a channel pool with confirms");
+            return Err(AmqpError::MandatoryWithoutConfirmsError);
+        }
+
+        Ok(Arc::new(RabbitMQPublisher {
+            confirms: pool.has_confirms(),
+            channel: PublisherChannel::Pool(pool),
+            mandatory,
+            confirm_timeout: timeout,
+        }))
     }
 }
 
@@ -44,20 +115,27 @@ impl Publisher for RabbitMQPublisher {
             self.btree_map(&infos.headers.clone().unwrap(), &mut btree);
         }
 
-        match self
-            .channel
+        let channel = match &self.channel {
+            PublisherChannel::Single(channel) => channel.clone(),
+            PublisherChannel::Pool(pool) => match pool.get().await {
+                Err(err) => {
+                    error!(error = err.to_string(), "failure to get a pooled channel");
+                    Err(MessagingError::PublisherError)
+                }
+                Ok(c) => Ok(c),
+            }?,
+        };
+
+        let confirm = match channel
             .basic_publish(
                 &infos.to,
                 &infos.key,
                 BasicPublishOptions {
                     immediate: false,
-                    mandatory: false,
+                    mandatory: self.mandatory,
                 },
                 &infos.data,
-                BasicProperties::default()
-                    .with_content_type(ShortString::from(JSON_CONTENT_TYPE))
-                    .with_type(ShortString::from(infos.msg_type.clone()))
-                    .with_message_id(ShortString::from(Uuid::new_v4().to_string()))
+                basic_properties(&infos.msg_type, &infos.properties)
                     .with_headers(FieldTable::from(btree)),
             )
             .await
@@ -66,12 +144,123 @@ impl Publisher for RabbitMQPublisher {
                 error!(error = err.to_string(), "error publishing message");
                 Err(MessagingError::PublisherError)
             }
-            _ => Ok(()),
+            Ok(c) => Ok(c),
+        }?;
+
+        if !self.confirms {
+            return Ok(());
         }
+
+        let confirmation = match tokio::time::timeout(self.confirm_timeout, confirm).await {
+            Err(_) => {
+                error!(
+                    exchange = infos.to,
+                    key = infos.key,
+                    "timeout waiting for publisher confirmation"
+                );
+                Err(MessagingError::PublisherTimeoutError)
+            }
+            Ok(Err(err)) => {
+                error!(
+                    error = err.to_string(),
+                    "error waiting publisher confirmation"
+                );
+                Err(MessagingError::PublisherError)
+            }
+            Ok(Ok(c)) => Ok(c),
+        }?;
+
+        self.confirmation_result(infos, confirmation)
+    }
+}
+
+fn basic_properties(msg_type: &str, props: &MessageProperties) -> BasicProperties {
+    let message_id = match &props.message_id {
+        Some(id) => id.clone(),
+        None => Uuid::new_v4().to_string(),
+    };
+
+    let content_type = match &props.content_type {
+        Some(content_type) => content_type.as_str(),
+        None => JSON_CONTENT_TYPE,
+    };
+
+    let timestamp = match props.timestamp {
+        Some(ts) => ts,
+        None => SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default(),
+    };
+
+    let delivery_mode = match props.transient {
+        true => AMQP_DELIVERY_MODE_TRANSIENT,
+        false => AMQP_DELIVERY_MODE_PERSISTENT,
+    };
+
+    let mut properties = BasicProperties::default()
+        .with_content_type(ShortString::from(content_type))
+        .with_type(ShortString::from(msg_type))
+        .with_message_id(ShortString::from(message_id))
+        .with_timestamp(timestamp)
+        .with_delivery_mode(delivery_mode);
+
+    if let Some(id) = &props.correlation_id {
+        properties = properties.with_correlation_id(ShortString::from(id.clone()));
+    }
+
+    if let Some(reply_to) = &props.reply_to {
+        properties = properties.with_reply_to(ShortString::from(reply_to.clone()));
+    }
+
+    if let Some(app_id) = &props.app_id {
+        properties = properties.with_app_id(ShortString::from(app_id.clone()));
     }
+
+    // the expiration property is the ttl in milliseconds as a string
+    if let Some(ttl) = props.ttl {
+        properties = properties.with_expiration(ShortString::from(ttl.as_millis().to_string()));
+    }
+
+    if let Some(priority) = props.priority {
+        properties = properties.with_priority(priority);
+    }
+
+    properties
 }
 
 impl RabbitMQPublisher {
+    fn confirmation_result(
+        &self,
+        infos: &PublishMessage,
+        confirmation: Confirmation,
+    ) -> Result<(), MessagingError> {
+        match confirmation {
+            Confirmation::Ack(None) | Confirmation::NotRequested => {
+                debug!(exchange = infos.to, key = infos.key, "message confirmed");
+                Ok(())
+            }
+            Confirmation::Ack(Some(returned)) | Confirmation::Nack(Some(returned)) => {
+                let reason = format!("{} - {}", returned.reply_code, returned.reply_text);
+                error!(
+                    exchange = infos.to,
+                    key = infos.key,
+                    reason = reason,
+                    "message returned by the broker"
+                );
+                Err(MessagingError::PublisherReturnedError(reason))
+            }
+            Confirmation::Nack(None) => {
+                error!(
+                    exchange = infos.to,
+                    key = infos.key,
+                    "message rejected by the broker"
+                );
+                Err(MessagingError::PublisherNackError)
+            }
+        }
+    }
+
     fn btree_map(
         &self,
         hash_map: &HashMap<String, HeaderValues>,
@@ -93,3 +282,51 @@ impl RabbitMQPublisher {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_use_default_properties() {
+        let props = basic_properties("order-created", &MessageProperties::default());
+
+        assert_eq!(
+            props.content_type(),
+            &Some(ShortString::from(JSON_CONTENT_TYPE))
+        );
+        assert_eq!(props.kind(), &Some(ShortString::from("order-created")));
+        assert_eq!(props.delivery_mode(), &Some(AMQP_DELIVERY_MODE_PERSISTENT));
+        assert!(props.message_id().is_some());
+        assert!(props.timestamp().is_some());
+        assert!(props.expiration().is_none());
+        assert!(props.priority().is_none());
+    }
+
+    #[test]
+    fn should_use_message_properties() {
+        let msg = PublishMessage::new("exchange", "exchange", "key", "order-created", &[], None)
+            .with_message_id("order-1")
+            .with_correlation_id("request-1")
+            .with_reply_to("replies")
+            .with_app_id("orders")
+            .with_timestamp(1700000000)
+            .with_ttl(Duration::from_secs(30))
+            .with_priority(5)
+            .transient();
+
+        let props = basic_properties(&msg.msg_type, &msg.properties);
+
+        assert_eq!(props.message_id(), &Some(ShortString::from("order-1")));
+        assert_eq!(
+            props.correlation_id(),
+            &Some(ShortString::from("request-1"))
+        );
+        assert_eq!(props.reply_to(), &Some(ShortString::from("replies")));
+        assert_eq!(props.app_id(), &Some(ShortString::from("orders")));
+        assert_eq!(props.timestamp(), &Some(1700000000));
+        assert_eq!(props.expiration(), &Some(ShortString::from("30000")));
+        assert_eq!(props.priority(), &Some(5));
+        assert_eq!(props.delivery_mode(), &Some(AMQP_DELIVERY_MODE_TRANSIENT));
+    }
+}
@@ -1,3 +1,72 @@
+use crate::errors::AmqpError;
+use lapin::types::{AMQPValue, LongLongInt, LongString, ShortString};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt::Display};
+
+pub const AMQP_HEADERS_MATCH: &str = "x-match";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QueueKind {
+    #[default]
+    Classic,
+    Quorum,
+    Stream,
+}
+
+impl Display for QueueKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueueKind::Classic => write!(f, "classic"),
+            QueueKind::Quorum => write!(f, "quorum"),
+            QueueKind::Stream => write!(f, "stream"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum QueueOverflow {
+    DropHead,
+    RejectPublish,
+    RejectPublishDlx,
+}
+
+impl Display for QueueOverflow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueueOverflow::DropHead => write!(f, "drop-head"),
+            QueueOverflow::RejectPublish => write!(f, "reject-publish"),
+            QueueOverflow::RejectPublishDlx => write!(f, "reject-publish-dlx"),
+        }
+    }
+}
+
+///Where a stream consumer starts reading
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamOffset {
+    First,
+    Last,
+    Next,
+    ///Absolute offset in the stream
+    Offset(i64),
+    ///Unix timestamp in seconds
+    Timestamp(u64),
+}
+
+impl From<&StreamOffset> for AMQPValue {
+    fn from(offset: &StreamOffset) -> Self {
+        match offset {
+            StreamOffset::First => AMQPValue::LongString(LongString::from("first")),
+            StreamOffset::Last => AMQPValue::LongString(LongString::from("last")),
+            StreamOffset::Next => AMQPValue::LongString(LongString::from("next")),
+            StreamOffset::Offset(offset) => AMQPValue::LongLongInt(LongLongInt::from(*offset)),
+            StreamOffset::Timestamp(ts) => AMQPValue::Timestamp(*ts),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct QueueDefinition {
     pub(crate) name: String,
@@ -11,6 +80,22 @@ pub struct QueueDefinition {
     pub(crate) retry_name: Option<String>,
     pub(crate) retry_ttl: Option<i32>,
     pub(crate) retries: Option<i32>,
+    pub(crate) retry_backoff: Vec<i32>,
+    pub(crate) prefetch_count: Option<u16>,
+    pub(crate) consumer_tag: Option<String>,
+    pub(crate) consumer_exclusive: bool,
+    pub(crate) consumer_priority: Option<i32>,
+    pub(crate) kind: QueueKind,
+    pub(crate) delivery_limit: Option<i32>,
+    pub(crate) max_length: Option<i64>,
+    pub(crate) max_length_bytes: Option<i64>,
+    pub(crate) overflow: Option<QueueOverflow>,
+    pub(crate) max_priority: Option<u8>,
+    pub(crate) single_active_consumer: bool,
+    pub(crate) lazy: bool,
+    pub(crate) expires: Option<i32>,
+    pub(crate) max_age: Option<String>,
+    pub(crate) stream_offset: Option<StreamOffset>,
 }
 
 impl QueueDefinition {
@@ -27,6 +112,22 @@ impl QueueDefinition {
             retry_name: None,
             retry_ttl: None,
             retries: None,
+            retry_backoff: vec![],
+            prefetch_count: None,
+            consumer_tag: None,
+            consumer_exclusive: false,
+            consumer_priority: None,
+            kind: QueueKind::Classic,
+            delivery_limit: None,
+            max_length: None,
+            max_length_bytes: None,
+            overflow: None,
+            max_priority: None,
+            single_active_consumer: false,
+            lazy: false,
+            expires: None,
+            max_age: None,
+            stream_offset: None,
         }
     }
 
@@ -61,12 +162,224 @@ impl QueueDefinition {
         self.retry_ttl = Some(ttl);
         self
     }
+
+    ///One retry queue per ttl, the n-th failure waits in `<name>-retry-<n>`
+    pub fn with_backoff(mut self, ttls: &[i32]) -> Self {
+        self.retry_backoff = ttls.to_vec();
+        self
+    }
+
+    ///Retry tiers of `initial_ttl * multiplier^n` milliseconds
+    pub fn with_exponential_backoff(self, initial_ttl: i32, multiplier: i32, retries: i32) -> Self {
+        let mut ttls = vec![];
+        let mut ttl = initial_ttl;
+        for _ in 0..retries {
+            ttls.push(ttl);
+            ttl = ttl.saturating_mul(multiplier);
+        }
+
+        self.with_backoff(&ttls)
+    }
+
+    pub(crate) fn retry_tier_name(&self, tier: usize) -> String {
+        format!("{}-retry-{}", self.name, tier)
+    }
+
+    ///Maximum number of unacknowledged messages delivered to the consumer of this queue
+    pub fn prefetch(mut self, count: u16) -> Self {
+        self.prefetch_count = Some(count);
+        self
+    }
+
+    ///Suffixed with `-{msg_type}` when several msg_types are consumed from the queue.
+    ///Default: `{queue}-{msg_type}-{uuid}`
+    pub fn consumer_tag(mut self, tag: &str) -> Self {
+        self.consumer_tag = Some(tag.to_owned());
+        self
+    }
+
+    ///Only allowed when a single msg_type is consumed from the queue
+    pub fn exclusive_consumer(mut self) -> Self {
+        self.consumer_exclusive = true;
+        self
+    }
+
+    pub fn consumer_priority(mut self, priority: i32) -> Self {
+        self.consumer_priority = Some(priority);
+        self
+    }
+
+    ///Quorum queues are always durable
+    pub fn quorum(mut self) -> Self {
+        self.kind = QueueKind::Quorum;
+        self.durable = true;
+        self
+    }
+
+    ///Quorum queues only: number of redeliveries before the message is dead-lettered
+    pub fn delivery_limit(mut self, limit: i32) -> Self {
+        self.delivery_limit = Some(limit);
+        self
+    }
+
+    pub fn max_length(mut self, length: i64) -> Self {
+        self.max_length = Some(length);
+        self
+    }
+
+    pub fn max_length_bytes(mut self, bytes: i64) -> Self {
+        self.max_length_bytes = Some(bytes);
+        self
+    }
+
+    ///Behaviour when max_length or max_length_bytes is reached. Default: DropHead
+    pub fn overflow(mut self, overflow: QueueOverflow) -> Self {
+        self.overflow = Some(overflow);
+        self
+    }
+
+    pub fn max_priority(mut self, priority: u8) -> Self {
+        self.max_priority = Some(priority);
+        self
+    }
+
+    pub fn single_active_consumer(mut self) -> Self {
+        self.single_active_consumer = true;
+        self
+    }
+
+    ///Classic queues only
+    pub fn lazy(mut self) -> Self {
+        self.lazy = true;
+        self
+    }
+
+    ///Milliseconds the queue can be unused before it is deleted
+    pub fn expires(mut self, expires: i32) -> Self {
+        self.expires = Some(expires);
+        self
+    }
+
+    ///Stream queues are always durable, their consumers require a `prefetch`
+    pub fn stream(mut self) -> Self {
+        self.kind = QueueKind::Stream;
+        self.durable = true;
+        self
+    }
+
+    ///Streams only: retention as a duration, e.g. `7D`, `12h`
+    pub fn max_age(mut self, age: &str) -> Self {
+        self.max_age = Some(age.to_owned());
+        self
+    }
+
+    ///Streams only. Default: Next
+    pub fn stream_offset(mut self, offset: StreamOffset) -> Self {
+        self.stream_offset = Some(offset);
+        self
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), AmqpError> {
+        let invalid = |reason: &str| {
+            Err(AmqpError::InvalidQueueDefinitionError(
+                self.name.clone(),
+                reason.to_owned(),
+            ))
+        };
+
+        if self.name.is_empty() {
+            return invalid("queue name is required");
+        }
+
+        if self.kind == QueueKind::Quorum {
+            if !self.durable {
+                return invalid("quorum queues must be durable");
+            }
+            if self.exclusive {
+                return invalid("quorum queues can not be exclusive");
+            }
+            if self.delete {
+                return invalid("quorum queues can not be auto-deleted");
+            }
+            if self.lazy {
+                return invalid("lazy mode is only supported by classic queues");
+            }
+            if self.max_priority.is_some() {
+                return invalid("priority is only supported by classic queues");
+            }
+            if self.overflow == Some(QueueOverflow::RejectPublishDlx) {
+                return invalid("reject-publish-dlx is not supported by quorum queues");
+            }
+        }
+
+        if self.kind == QueueKind::Stream {
+            if !self.durable {
+                return invalid("stream queues must be durable");
+            }
+            if self.exclusive || self.delete {
+                return invalid("stream queues can not be exclusive or auto-deleted");
+            }
+            if self.dlq_name.is_some()
+                || self.retry_name.is_some()
+                || !self.retry_backoff.is_empty()
+            {
+                return invalid("stream queues do not support dead-lettering");
+            }
+            if self.ttl.is_some() || self.max_length.is_some() || self.overflow.is_some() {
+                return invalid("stream queues only support max length bytes and max age");
+            }
+            if self.lazy || self.max_priority.is_some() || self.expires.is_some() {
+                return invalid("stream queues do not support lazy, priority or expires");
+            }
+        } else if self.stream_offset.is_some() || self.max_age.is_some() {
+            return invalid("stream offset and max age are only supported by streams");
+        }
+
+        if self.retry_name.is_some() && !self.retry_backoff.is_empty() {
+            return invalid("with_retry and backoff tiers can not be combined");
+        }
+
+        if self.retry_backoff.iter().any(|ttl| *ttl <= 0) {
+            return invalid("backoff ttls must be greater than zero");
+        }
+
+        if self.delivery_limit.is_some() && self.kind != QueueKind::Quorum {
+            return invalid("delivery limit is only supported by quorum queues");
+        }
+
+        if matches!(self.delivery_limit, Some(limit) if limit < 0) {
+            return invalid("delivery limit must be positive");
+        }
+
+        if matches!(self.max_length, Some(length) if length < 0) {
+            return invalid("max length must be positive");
+        }
+
+        if matches!(self.max_length_bytes, Some(bytes) if bytes < 0) {
+            return invalid("max length bytes must be positive");
+        }
+
+        if self.max_priority == Some(0) {
+            return invalid("max priority must be between 1 and 255");
+        }
+
+        if matches!(self.expires, Some(expires) if expires <= 0) {
+            return invalid("expires must be greater than zero");
+        }
+
+        if matches!(self.ttl, Some(ttl) if ttl < 0) {
+            return invalid("ttl must be positive");
+        }
+
+        Ok(())
+    }
 }
 
 pub struct QueueBinding<'qeb> {
     pub(crate) queue_name: &'qeb str,
     pub(crate) exchange_name: &'qeb str,
     pub(crate) routing_key: &'qeb str,
+    pub(crate) params: BTreeMap<ShortString, AMQPValue>,
 }
 
 impl<'qeb> QueueBinding<'qeb> {
@@ -75,6 +388,7 @@ impl<'qeb> QueueBinding<'qeb> {
             queue_name: queue,
             exchange_name: "",
             routing_key: "",
+            params: BTreeMap::default(),
         }
     }
 
@@ -87,4 +401,155 @@ impl<'qeb> QueueBinding<'qeb> {
         self.routing_key = key;
         self
     }
+
+    pub fn params(mut self, params: BTreeMap<ShortString, AMQPValue>) -> Self {
+        self.params = params;
+        self
+    }
+
+    pub fn param(mut self, key: ShortString, value: AMQPValue) -> Self {
+        self.params.insert(key, value);
+        self
+    }
+
+    ///Headers exchange: the message must match all the binding headers
+    pub fn match_all(mut self) -> Self {
+        self.params.insert(
+            ShortString::from(AMQP_HEADERS_MATCH),
+            AMQPValue::LongString(LongString::from("all")),
+        );
+        self
+    }
+
+    ///Headers exchange: the message must match at least one of the binding headers
+    pub fn match_any(mut self) -> Self {
+        self.params.insert(
+            ShortString::from(AMQP_HEADERS_MATCH),
+            AMQPValue::LongString(LongString::from("any")),
+        );
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_validate_classic_queue() {
+        let def = QueueDefinition::new("queue")
+            .durable()
+            .max_length(100)
+            .overflow(QueueOverflow::RejectPublishDlx)
+            .max_priority(10)
+            .lazy()
+            .expires(60_000);
+
+        assert!(def.validate().is_ok());
+    }
+
+    #[test]
+    fn should_validate_quorum_queue() {
+        let def = QueueDefinition::new("queue")
+            .quorum()
+            .delivery_limit(5)
+            .single_active_consumer();
+
+        assert!(def.durable);
+        assert!(def.validate().is_ok());
+    }
+
+    #[test]
+    fn should_reject_quorum_only_arguments_in_classic_queue() {
+        let def = QueueDefinition::new("queue").delivery_limit(5);
+
+        assert!(def.validate().is_err());
+    }
+
+    #[test]
+    fn should_reject_classic_only_arguments_in_quorum_queue() {
+        assert!(QueueDefinition::new("queue")
+            .quorum()
+            .lazy()
+            .validate()
+            .is_err());
+        assert!(QueueDefinition::new("queue")
+            .quorum()
+            .max_priority(5)
+            .validate()
+            .is_err());
+        assert!(QueueDefinition::new("queue")
+            .quorum()
+            .exclusive()
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn should_build_exponential_backoff() {
+        let def = QueueDefinition::new("queue").with_exponential_backoff(1000, 10, 4);
+
+        assert_eq!(def.retry_backoff, vec![1000, 10_000, 100_000, 1_000_000]);
+        assert_eq!(def.retry_tier_name(2), "queue-retry-2");
+        assert!(def.validate().is_ok());
+    }
+
+    #[test]
+    fn should_reject_retry_combined_with_backoff() {
+        let def = QueueDefinition::new("queue")
+            .with_retry(1000, 3)
+            .with_backoff(&[1000, 60_000]);
+
+        assert!(def.validate().is_err());
+    }
+
+    #[test]
+    fn should_validate_stream_queue() {
+        let def = QueueDefinition::new("events")
+            .stream()
+            .prefetch(100)
+            .max_age("7D")
+            .stream_offset(StreamOffset::First);
+        assert!(def.validate().is_ok());
+
+        assert!(QueueDefinition::new("events").stream().validate().is_ok());
+        assert!(QueueDefinition::new("events")
+            .stream()
+            .with_dlq()
+            .validate()
+            .is_err());
+        assert!(QueueDefinition::new("queue")
+            .stream_offset(StreamOffset::Last)
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn should_convert_stream_offsets() {
+        assert_eq!(
+            AMQPValue::from(&StreamOffset::First),
+            AMQPValue::LongString(LongString::from("first"))
+        );
+        assert_eq!(
+            AMQPValue::from(&StreamOffset::Offset(42)),
+            AMQPValue::LongLongInt(42)
+        );
+        assert_eq!(
+            AMQPValue::from(&StreamOffset::Timestamp(1700000000)),
+            AMQPValue::Timestamp(1700000000)
+        );
+    }
+
+    #[test]
+    fn should_reject_invalid_limits() {
+        assert!(QueueDefinition::new("queue")
+            .max_length(-1)
+            .validate()
+            .is_err());
+        assert!(QueueDefinition::new("queue")
+            .max_priority(0)
+            .validate()
+            .is_err());
+        assert!(QueueDefinition::new("queue").expires(0).validate().is_err());
+    }
 }
@@ -0,0 +1,255 @@
+use crate::errors::AmqpError;
+use configs::RabbitMQConfigs;
+use lapin::{
+    tcp::{HandshakeError, HandshakeResult, RustlsConnector, TcpStream},
+    uri::AMQPUri,
+};
+use rustls::{
+    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    crypto::{ring, verify_tls12_signature, verify_tls13_signature, CryptoProvider},
+    pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime},
+    ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme,
+};
+use std::{fs::File, io::BufReader, sync::Arc, time::Duration};
+use tracing::{error, warn};
+
+/// Builds the rustls connector from the TLS settings of `RabbitMQConfigs`.
+pub(crate) fn new_tls_connector(cfg: &RabbitMQConfigs) -> Result<RustlsConnector, AmqpError> {
+    let provider = Arc::new(ring::default_provider());
+
+    let builder = match ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+    {
+        Err(err) => {
+            error!(
+                error = err.to_string(),
+                "failure to configure tls protocols"
+            );
+            Err(AmqpError::TlsConfigError(err.to_string()))
+        }
+        Ok(b) => Ok(b),
+    }?;
+
+    let builder = if cfg.tls_verify {
+        builder.with_root_certificates(root_certificates(cfg)?)
+    } else {
+        warn!("rabbitmq tls certificate verification is disabled");
+        builder
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(NoCertificateVerification(provider)))
+    };
+
+    let config = if cfg.tls_cert_path.is_empty() {
+        builder.with_no_client_auth()
+    } else {
+        let certs = load_certificates(&cfg.tls_cert_path)?;
+        let key = load_private_key(&cfg.tls_private_key_path)?;
+
+        match builder.with_client_auth_cert(certs, key) {
+            Err(err) => {
+                error!(error = err.to_string(), "invalid tls client certificate");
+                Err(AmqpError::TlsConfigError(err.to_string()))
+            }
+            Ok(c) => Ok(c),
+        }?
+    };
+
+    Ok(RustlsConnector::from(config))
+}
+
+/// Connect function for `Connection::connector`
+pub(crate) fn connector(
+    connector: RustlsConnector,
+    server_name: String,
+) -> Box<dyn FnOnce(&AMQPUri) -> HandshakeResult + Send + Sync> {
+    // the unboxed handshake error is required by the lapin connector signature
+    #[allow(clippy::result_large_err)]
+    let handshake = move |uri: &AMQPUri| -> HandshakeResult {
+        connect(uri, &connector, &server_name).map_err(|err| *err)
+    };
+
+    Box::new(handshake)
+}
+
+/// Same as lapin's default TCP connect, with the TLS handshake made by `connector`
+/// so the server name can differ from the URI host.
+fn connect(
+    uri: &AMQPUri,
+    connector: &RustlsConnector,
+    server_name: &str,
+) -> Result<TcpStream, Box<HandshakeError>> {
+    let addr = format!("{}:{}", uri.authority.host, uri.authority.port);
+
+    let stream = match uri.query.connection_timeout {
+        Some(timeout) => TcpStream::connect_timeout(addr, Duration::from_millis(timeout)),
+        None => TcpStream::connect(addr),
+    }
+    .map_err(|err| Box::new(HandshakeError::from(err)))?;
+
+    let stream = stream
+        .into_rustls(connector, server_name)
+        .map_err(Box::new)?;
+
+    stream
+        .set_nonblocking(true)
+        .map_err(|err| Box::new(HandshakeError::from(err)))?;
+
+    Ok(stream)
+}
+
+fn root_certificates(cfg: &RabbitMQConfigs) -> Result<RootCertStore, AmqpError> {
+    let mut roots = RootCertStore::empty();
+
+    let certs = if cfg.tls_ca_cert_path.is_empty() {
+        match rustls_native_certs::load_native_certs() {
+            Err(err) => {
+                error!(
+                    error = err.to_string(),
+                    "failure to load system certificates"
+                );
+                Err(AmqpError::TlsConfigError(err.to_string()))
+            }
+            Ok(c) => Ok(c),
+        }?
+    } else {
+        load_certificates(&cfg.tls_ca_cert_path)?
+    };
+
+    let (_, ignored) = roots.add_parsable_certificates(certs);
+    if ignored > 0 {
+        warn!(
+            ignored = ignored,
+            "some ca certificates could not be parsed"
+        );
+    }
+
+    Ok(roots)
+}
+
+fn load_certificates(path: &str) -> Result<Vec<CertificateDer<'static>>, AmqpError> {
+    let mut reader = open(path)?;
+
+    match rustls_pemfile::certs(&mut reader).collect::<Result<Vec<_>, _>>() {
+        Err(err) => {
+            error!(
+                error = err.to_string(),
+                path = path,
+                "invalid pem certificate"
+            );
+            Err(AmqpError::TlsConfigError(path.to_owned()))
+        }
+        Ok(certs) => Ok(certs),
+    }
+}
+
+fn load_private_key(path: &str) -> Result<PrivateKeyDer<'static>, AmqpError> {
+    let mut reader = open(path)?;
+
+    match rustls_pemfile::private_key(&mut reader) {
+        Ok(Some(key)) => Ok(key),
+        Ok(None) => {
+            error!(path = path, "no private key found");
+            Err(AmqpError::TlsConfigError(path.to_owned()))
+        }
+        Err(err) => {
+            error!(
+                error = err.to_string(),
+                path = path,
+                "invalid pem private key"
+            );
+            Err(AmqpError::TlsConfigError(path.to_owned()))
+        }
+    }
+}
+
+fn open(path: &str) -> Result<BufReader<File>, AmqpError> {
+    match File::open(path) {
+        Err(err) => {
+            error!(
+                error = err.to_string(),
+                path = path,
+                "failure to open tls file"
+            );
+            Err(AmqpError::TlsConfigError(path.to_owned()))
+        }
+        Ok(f) => Ok(BufReader::new(f)),
+    }
+}
+
+#[derive(Debug)]
+struct NoCertificateVerification(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for NoCertificateVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_build_connector_without_verification() {
+        let cfg = RabbitMQConfigs {
+            tls: true,
+            tls_verify: false,
+            ..RabbitMQConfigs::default()
+        };
+
+        assert!(new_tls_connector(&cfg).is_ok());
+    }
+
+    #[test]
+    fn should_fail_when_ca_file_is_missing() {
+        let cfg = RabbitMQConfigs {
+            tls: true,
+            tls_ca_cert_path: "/not/found/ca.pem".to_owned(),
+            ..RabbitMQConfigs::default()
+        };
+
+        assert_eq!(
+            new_tls_connector(&cfg).err(),
+            Some(AmqpError::TlsConfigError("/not/found/ca.pem".to_owned()))
+        );
+    }
+}
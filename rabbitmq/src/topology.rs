@@ -1,23 +1,38 @@
 use crate::{
     errors::AmqpError,
     exchange::{ExchangeBinding, ExchangeDefinition},
-    queue::{QueueBinding, QueueDefinition},
+    queue::{QueueBinding, QueueDefinition, QueueKind},
 };
 use async_trait::async_trait;
 use lapin::{
-    options::{QueueBindOptions, QueueDeclareOptions},
-    types::{AMQPValue, FieldTable, LongInt, LongString, ShortString},
-    Channel,
+    options::{
+        ExchangeBindOptions, ExchangeDeclareOptions, ExchangeDeleteOptions, QueueBindOptions,
+        QueueDeclareOptions, QueueDeleteOptions,
+    },
+    protocol::{AMQPErrorKind, AMQPSoftError},
+    types::{AMQPValue, FieldTable, LongInt, LongLongInt, LongString, ShortString},
+    Channel, Connection,
 };
 use std::{
     collections::{BTreeMap, HashMap},
+    fmt::Display,
     sync::Arc,
 };
-use tracing::{debug, error};
+use tracing::{debug, error, warn};
 
 pub const AMQP_HEADERS_DEAD_LETTER_EXCHANGE: &str = "x-dead-letter-exchange";
 pub const AMQP_HEADERS_DEAD_LETTER_ROUTING_KEY: &str = "x-dead-letter-routing-key";
 pub const AMQP_HEADERS_MESSAGE_TTL: &str = "x-message-ttl";
+pub const AMQP_HEADERS_QUEUE_TYPE: &str = "x-queue-type";
+pub const AMQP_HEADERS_DELIVERY_LIMIT: &str = "x-delivery-limit";
+pub const AMQP_HEADERS_MAX_LENGTH: &str = "x-max-length";
+pub const AMQP_HEADERS_MAX_LENGTH_BYTES: &str = "x-max-length-bytes";
+pub const AMQP_HEADERS_OVERFLOW: &str = "x-overflow";
+pub const AMQP_HEADERS_MAX_PRIORITY: &str = "x-max-priority";
+pub const AMQP_HEADERS_SINGLE_ACTIVE_CONSUMER: &str = "x-single-active-consumer";
+pub const AMQP_HEADERS_QUEUE_MODE: &str = "x-queue-mode";
+pub const AMQP_HEADERS_EXPIRES: &str = "x-expires";
+pub const AMQP_HEADERS_MAX_AGE: &str = "x-max-age";
 
 #[async_trait]
 pub trait Topology<'tp> {
@@ -26,6 +41,83 @@ pub trait Topology<'tp> {
     fn exchange_binding(self, binding: &'tp ExchangeBinding) -> Self;
     fn queue_binding(self, binding: &'tp QueueBinding) -> Self;
     async fn install(&self) -> Result<(), AmqpError>;
+    /// Dry-run of `install`, each exchange and queue is checked on a short-lived channel
+    /// from `conn` because a failed check closes the channel it was made on. Missing ones
+    /// are found with a passive declaration; existing ones are then redeclared with the
+    /// declared arguments, a no-op unless the broker rejects them with the mismatch reason.
+    /// Passive definitions are only checked for existence.
+    async fn diff(&self, conn: &Connection) -> Result<Vec<TopologyChange>, AmqpError>;
+    /// Fails with `AmqpError::TopologyMismatchError` when `diff` reports any change.
+    async fn verify(&self, conn: &Connection) -> Result<(), AmqpError>;
+    /// Deletes the declared queues, including retry and dlq queues, and exchanges.
+    /// Passive definitions are not owned by the topology and are kept.
+    /// Bindings are removed by the broker together with them.
+    async fn teardown(&self) -> Result<(), AmqpError>;
+}
+
+/// AMQP 0-9-1 has no passive binding check, so bindings are only reported when
+/// one of their ends does not exist yet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TopologyChange {
+    CreateExchange(String),
+    CreateQueue(String),
+    BindExchange {
+        source: String,
+        destination: String,
+        routing_key: String,
+    },
+    BindQueue {
+        queue: String,
+        exchange: String,
+        routing_key: String,
+    },
+    ExchangeMismatch {
+        name: String,
+        reason: String,
+    },
+    QueueMismatch {
+        name: String,
+        reason: String,
+    },
+}
+
+impl Display for TopologyChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TopologyChange::CreateExchange(name) => write!(f, "create exchange `{}`", name),
+            TopologyChange::CreateQueue(name) => write!(f, "create queue `{}`", name),
+            TopologyChange::BindExchange {
+                source,
+                destination,
+                routing_key,
+            } => write!(
+                f,
+                "bind exchange `{}` to exchange `{}` with key `{}`",
+                destination, source, routing_key
+            ),
+            TopologyChange::BindQueue {
+                queue,
+                exchange,
+                routing_key,
+            } => write!(
+                f,
+                "bind queue `{}` to exchange `{}` with key `{}`",
+                queue, exchange, routing_key
+            ),
+            TopologyChange::ExchangeMismatch { name, reason } => {
+                write!(f, "exchange `{}` mismatch - {}", name, reason)
+            }
+            TopologyChange::QueueMismatch { name, reason } => {
+                write!(f, "queue `{}` mismatch - {}", name, reason)
+            }
+        }
+    }
+}
+
+pub(crate) struct QueueDeclaration {
+    pub(crate) name: String,
+    pub(crate) options: QueueDeclareOptions,
+    pub(crate) args: BTreeMap<ShortString, AMQPValue>,
 }
 
 pub struct AmqpTopology<'tp> {
@@ -33,7 +125,7 @@ pub struct AmqpTopology<'tp> {
     pub(crate) queues: HashMap<&'tp str, &'tp QueueDefinition>,
     pub(crate) queues_binding: Vec<&'tp QueueBinding<'tp>>,
     pub(crate) exchanges: Vec<&'tp ExchangeDefinition<'tp>>,
-    pub(crate) exchanges_binding: Vec<&'tp ExchangeBinding>,
+    pub(crate) exchanges_binding: Vec<&'tp ExchangeBinding<'tp>>,
 }
 
 impl<'tp> AmqpTopology<'tp> {
@@ -76,6 +168,124 @@ impl<'tp> Topology<'tp> for AmqpTopology<'tp> {
         self.binding_exchanges().await?;
         self.binding_queues().await
     }
+
+    async fn diff(&self, conn: &Connection) -> Result<Vec<TopologyChange>, AmqpError> {
+        let mut changes = vec![];
+
+        for exch in self.exchanges.clone() {
+            if let Some(change) = self.diff_exchange(conn, exch).await? {
+                changes.push(change);
+            }
+        }
+
+        for def in self.queues.values() {
+            def.validate()?;
+
+            for declaration in queue_declarations(def) {
+                if let Some(change) = self.diff_queue(conn, &declaration).await? {
+                    changes.push(change);
+                }
+            }
+        }
+
+        let created = |name: &str| {
+            changes.iter().any(|change| match change {
+                TopologyChange::CreateExchange(n) | TopologyChange::CreateQueue(n) => n == name,
+                _ => false,
+            })
+        };
+
+        let mut bindings = vec![];
+
+        for binding in self.exchanges_binding.clone() {
+            if created(binding.source) || created(binding.destination) {
+                bindings.push(TopologyChange::BindExchange {
+                    source: binding.source.to_owned(),
+                    destination: binding.destination.to_owned(),
+                    routing_key: binding.routing_key.to_owned(),
+                });
+            }
+        }
+
+        for binding in self.queues_binding.clone() {
+            if created(binding.exchange_name) || created(binding.queue_name) {
+                bindings.push(TopologyChange::BindQueue {
+                    queue: binding.queue_name.to_owned(),
+                    exchange: binding.exchange_name.to_owned(),
+                    routing_key: binding.routing_key.to_owned(),
+                });
+            }
+        }
+
+        changes.append(&mut bindings);
+
+        Ok(changes)
+    }
+
+    async fn verify(&self, conn: &Connection) -> Result<(), AmqpError> {
+        let changes = self.diff(conn).await?;
+
+        if changes.is_empty() {
+            debug!("topology verified");
+            return Ok(());
+        }
+
+        for change in &changes {
+            error!(change = change.to_string(), "topology mismatch");
+        }
+
+        Err(AmqpError::TopologyMismatchError(
+            changes
+                .iter()
+                .map(|c| c.to_string())
+                .collect::<Vec<String>>()
+                .join("; "),
+        ))
+    }
+
+    async fn teardown(&self) -> Result<(), AmqpError> {
+        for def in self.queues.values() {
+            for declaration in queue_declarations(def) {
+                if declaration.options.passive {
+                    continue;
+                }
+
+                debug!("deleting queue: {}", declaration.name);
+
+                match self
+                    .channel
+                    .queue_delete(&declaration.name, QueueDeleteOptions::default())
+                    .await
+                {
+                    Err(err) => {
+                        error!(error = err.to_string(), "error to delete the queue");
+                        Err(AmqpError::DeleteQueueError(declaration.name.clone()))
+                    }
+                    _ => Ok(()),
+                }?;
+            }
+        }
+
+        for exch in self.exchanges.iter().filter(|exch| !exch.passive) {
+            debug!("deleting exchange: {}", exch.name);
+
+            match self
+                .channel
+                .exchange_delete(exch.name, ExchangeDeleteOptions::default())
+                .await
+            {
+                Err(err) => {
+                    error!(error = err.to_string(), "error to delete the exchange");
+                    Err(AmqpError::DeleteExchangeError(exch.name.to_owned()))
+                }
+                _ => Ok(()),
+            }?;
+        }
+
+        debug!("topology was deleted");
+
+        Ok(())
+    }
 }
 
 impl<'tp> AmqpTopology<'tp> {
@@ -87,14 +297,8 @@ impl<'tp> AmqpTopology<'tp> {
                 .channel
                 .exchange_declare(
                     exch.name,
-                    exch.kind.clone().try_into().unwrap(),
-                    lapin::options::ExchangeDeclareOptions {
-                        passive: exch.passive,
-                        durable: exch.durable,
-                        auto_delete: exch.delete,
-                        internal: exch.internal,
-                        nowait: exch.no_wait,
-                    },
+                    exch.kind.clone().try_into()?,
+                    exchange_options(exch, exch.passive),
                     FieldTable::from(exch.params.clone()),
                 )
                 .await
@@ -105,7 +309,9 @@ impl<'tp> AmqpTopology<'tp> {
                         name = exch.name,
                         "error to declare the exchange"
                     );
-                    Err(AmqpError::DeclareExchangeError(err.to_string()))
+                    Err(AmqpError::DeclareExchangeError(declare_error(
+                        exch.name, &err,
+                    )))
                 }
                 _ => Ok(()),
             }?;
@@ -120,152 +326,212 @@ impl<'tp> AmqpTopology<'tp> {
         for (name, def) in self.queues.clone() {
             debug!("creating queue: {}", name);
 
-            let mut queue_args = BTreeMap::new();
-
-            if def.retry_name.is_some() {
-                self.declare_retry(def, &mut queue_args).await?;
-            }
-
-            if def.dlq_name.is_some() {
-                self.declare_dql(def, &mut queue_args).await?;
-            }
-
-            if def.ttl.is_some() {
-                queue_args.insert(
-                    ShortString::from(AMQP_HEADERS_MESSAGE_TTL),
-                    AMQPValue::LongInt(LongInt::from(def.ttl.unwrap())),
-                );
+            def.validate()?;
+
+            for declaration in queue_declarations(def) {
+                match self
+                    .channel
+                    .queue_declare(
+                        &declaration.name,
+                        declaration.options,
+                        FieldTable::from(declaration.args),
+                    )
+                    .await
+                {
+                    Err(err) => {
+                        error!(error = err.to_string(), "failure to declare queue");
+                        Err(AmqpError::DeclareQueueError(declare_error(
+                            &declaration.name,
+                            &err,
+                        )))
+                    }
+                    _ => {
+                        debug!("queue: {} was created", declaration.name);
+                        Ok(())
+                    }
+                }?;
             }
-
-            match self
-                .channel
-                .queue_declare(
-                    name,
-                    QueueDeclareOptions {
-                        passive: def.passive,
-                        durable: def.durable,
-                        exclusive: def.exclusive,
-                        auto_delete: def.delete,
-                        nowait: def.no_wait,
-                    },
-                    FieldTable::from(queue_args),
-                )
-                .await
-            {
-                Err(err) => {
-                    error!(error = err.to_string(), "");
-                    Err(AmqpError::DeclareQueueError(name.to_owned()))
-                }
-                _ => {
-                    debug!("queue: {} was created", name);
-                    Ok(())
-                }
-            }?;
         }
 
         Ok(())
     }
 
-    async fn declare_retry(
+    async fn diff_exchange(
         &self,
-        def: &QueueDefinition,
-        queue_args: &mut BTreeMap<ShortString, AMQPValue>,
-    ) -> Result<(), AmqpError> {
-        let mut args = BTreeMap::new();
-
-        args.insert(
-            ShortString::from(AMQP_HEADERS_DEAD_LETTER_EXCHANGE),
-            AMQPValue::LongString(LongString::from("")),
-        );
-        args.insert(
-            ShortString::from(AMQP_HEADERS_DEAD_LETTER_ROUTING_KEY),
-            AMQPValue::LongString(LongString::from(def.name.clone())),
-        );
-        args.insert(
-            ShortString::from(AMQP_HEADERS_MESSAGE_TTL),
-            AMQPValue::LongInt(LongInt::from(def.retry_ttl.unwrap())),
-        );
+        conn: &Connection,
+        exch: &ExchangeDefinition<'tp>,
+    ) -> Result<Option<TopologyChange>, AmqpError> {
+        let channel = check_channel(conn).await?;
+
+        if let Err(err) = channel
+            .exchange_declare(
+                exch.name,
+                exch.kind.clone().try_into()?,
+                exchange_options(exch, true),
+                FieldTable::default(),
+            )
+            .await
+        {
+            return match declare_failure(&err) {
+                Some(_) if is_not_found(&err) => {
+                    Ok(Some(TopologyChange::CreateExchange(exch.name.to_owned())))
+                }
+                Some(reason) => Ok(Some(TopologyChange::ExchangeMismatch {
+                    name: exch.name.to_owned(),
+                    reason,
+                })),
+                None => {
+                    error!(error = err.to_string(), "failure to check the exchange");
+                    Err(AmqpError::DeclareExchangeError(declare_error(
+                        exch.name, &err,
+                    )))
+                }
+            };
+        }
 
-        let retry_name = def.retry_name.clone().unwrap();
+        // passive definitions are not owned by the topology, only their existence is checked
+        if exch.passive {
+            close_check_channel(&channel).await;
+            return Ok(None);
+        }
 
-        match self
-            .channel
-            .queue_declare(
-                &retry_name,
-                QueueDeclareOptions {
-                    passive: def.passive,
-                    durable: def.durable,
-                    exclusive: def.exclusive,
-                    auto_delete: def.delete,
-                    nowait: def.no_wait,
-                },
-                FieldTable::from(args),
+        // the exchange exists, redeclaring it is a no-op unless the arguments differ
+        match channel
+            .exchange_declare(
+                exch.name,
+                exch.kind.clone().try_into()?,
+                exchange_options(exch, false),
+                FieldTable::from(exch.params.clone()),
             )
             .await
         {
-            Err(err) => {
-                error!(error = err.to_string(), "failure to declare retry queue");
-                Err(AmqpError::DeclareQueueError(retry_name))
-            }
+            Err(err) => match declare_failure(&err) {
+                Some(reason) => Ok(Some(TopologyChange::ExchangeMismatch {
+                    name: exch.name.to_owned(),
+                    reason,
+                })),
+                None => {
+                    error!(error = err.to_string(), "failure to check the exchange");
+                    Err(AmqpError::DeclareExchangeError(declare_error(
+                        exch.name, &err,
+                    )))
+                }
+            },
             _ => {
-                queue_args.insert(
-                    ShortString::from(AMQP_HEADERS_DEAD_LETTER_EXCHANGE),
-                    AMQPValue::LongString(LongString::from("")),
-                );
-
-                queue_args.insert(
-                    ShortString::from(AMQP_HEADERS_DEAD_LETTER_ROUTING_KEY),
-                    AMQPValue::LongString(LongString::from(retry_name)),
-                );
-                Ok(())
+                close_check_channel(&channel).await;
+                Ok(None)
             }
         }
     }
 
-    async fn declare_dql(
+    async fn diff_queue(
         &self,
-        def: &QueueDefinition,
-        queue_args: &mut BTreeMap<ShortString, AMQPValue>,
-    ) -> Result<(), AmqpError> {
-        let dlq_name = def.dlq_name.clone().unwrap();
+        conn: &Connection,
+        declaration: &QueueDeclaration,
+    ) -> Result<Option<TopologyChange>, AmqpError> {
+        let channel = check_channel(conn).await?;
 
-        match self
-            .channel
+        if let Err(err) = channel
             .queue_declare(
-                &dlq_name,
+                &declaration.name,
                 QueueDeclareOptions {
-                    passive: def.passive,
-                    durable: def.durable,
-                    exclusive: def.exclusive,
-                    auto_delete: def.delete,
-                    nowait: def.no_wait,
+                    passive: true,
+                    ..declaration.options
                 },
                 FieldTable::default(),
             )
             .await
         {
-            Err(err) => {
-                error!(error = err.to_string(), "failure to declare retry queue");
-                Err(AmqpError::DeclareQueueError(dlq_name))
-            }
-            _ => {
-                if def.retry_name.is_none() {
-                    queue_args.insert(
-                        ShortString::from(AMQP_HEADERS_DEAD_LETTER_EXCHANGE),
-                        AMQPValue::LongString(LongString::from("")),
-                    );
+            return match declare_failure(&err) {
+                Some(_) if is_not_found(&err) => {
+                    Ok(Some(TopologyChange::CreateQueue(declaration.name.clone())))
+                }
+                Some(reason) => Ok(Some(TopologyChange::QueueMismatch {
+                    name: declaration.name.clone(),
+                    reason,
+                })),
+                None => {
+                    error!(error = err.to_string(), "failure to check the queue");
+                    Err(AmqpError::DeclareQueueError(declare_error(
+                        &declaration.name,
+                        &err,
+                    )))
+                }
+            };
+        }
 
-                    queue_args.insert(
-                        ShortString::from(AMQP_HEADERS_DEAD_LETTER_ROUTING_KEY),
-                        AMQPValue::LongString(LongString::from(def.name.clone())),
-                    );
+        // passive definitions are not owned by the topology, only their existence is checked
+        if declaration.options.passive {
+            close_check_channel(&channel).await;
+            return Ok(None);
+        }
+
+        // the queue exists, redeclaring it is a no-op unless the arguments differ
+        match channel
+            .queue_declare(
+                &declaration.name,
+                declaration.options,
+                FieldTable::from(declaration.args.clone()),
+            )
+            .await
+        {
+            Err(err) => match declare_failure(&err) {
+                Some(reason) => Ok(Some(TopologyChange::QueueMismatch {
+                    name: declaration.name.clone(),
+                    reason,
+                })),
+                None => {
+                    error!(error = err.to_string(), "failure to check the queue");
+                    Err(AmqpError::DeclareQueueError(declare_error(
+                        &declaration.name,
+                        &err,
+                    )))
                 }
-                Ok(())
+            },
+            _ => {
+                close_check_channel(&channel).await;
+                Ok(None)
             }
         }
     }
 
     async fn binding_exchanges(&self) -> Result<(), AmqpError> {
+        for binding in self.exchanges_binding.clone() {
+            debug!(
+                "binding exchange: {} to the exchange: {} with the key: {}",
+                binding.destination, binding.source, binding.routing_key
+            );
+
+            match self
+                .channel
+                .exchange_bind(
+                    binding.destination,
+                    binding.source,
+                    binding.routing_key,
+                    ExchangeBindOptions {
+                        nowait: binding.no_wait,
+                    },
+                    FieldTable::from(binding.params.clone()),
+                )
+                .await
+            {
+                Err(err) => {
+                    error!(
+                        error = err.to_string(),
+                        "error to bind exchange to exchange"
+                    );
+
+                    Err(AmqpError::BindingExchangeToExchangeError(
+                        binding.source.to_owned(),
+                        binding.destination.to_owned(),
+                    ))
+                }
+                _ => Ok(()),
+            }?;
+        }
+
+        debug!("exchanges was bounded");
+
         Ok(())
     }
 
@@ -283,7 +549,7 @@ impl<'tp> AmqpTopology<'tp> {
                     binding.exchange_name,
                     binding.routing_key,
                     QueueBindOptions { nowait: false },
-                    FieldTable::default(),
+                    FieldTable::from(binding.params.clone()),
                 )
                 .await
             {
@@ -304,3 +570,373 @@ impl<'tp> AmqpTopology<'tp> {
         Ok(())
     }
 }
+
+/// Retry and dlq queues come before the queue that dead-letters into them.
+pub(crate) fn queue_declarations(def: &QueueDefinition) -> Vec<QueueDeclaration> {
+    let options = QueueDeclareOptions {
+        passive: def.passive,
+        durable: def.durable,
+        exclusive: def.exclusive,
+        auto_delete: def.delete,
+        nowait: def.no_wait,
+    };
+
+    let mut declarations = vec![];
+    let mut queue_args = queue_arguments(def);
+
+    if let Some(retry_name) = &def.retry_name {
+        let mut args = BTreeMap::new();
+        args.insert(
+            ShortString::from(AMQP_HEADERS_DEAD_LETTER_EXCHANGE),
+            AMQPValue::LongString(LongString::from("")),
+        );
+        args.insert(
+            ShortString::from(AMQP_HEADERS_DEAD_LETTER_ROUTING_KEY),
+            AMQPValue::LongString(LongString::from(def.name.clone())),
+        );
+        args.insert(
+            ShortString::from(AMQP_HEADERS_MESSAGE_TTL),
+            AMQPValue::LongInt(LongInt::from(def.retry_ttl.unwrap_or_default())),
+        );
+
+        declarations.push(QueueDeclaration {
+            name: retry_name.clone(),
+            options,
+            args,
+        });
+
+        queue_args.insert(
+            ShortString::from(AMQP_HEADERS_DEAD_LETTER_EXCHANGE),
+            AMQPValue::LongString(LongString::from("")),
+        );
+        queue_args.insert(
+            ShortString::from(AMQP_HEADERS_DEAD_LETTER_ROUTING_KEY),
+            AMQPValue::LongString(LongString::from(retry_name.clone())),
+        );
+    }
+
+    // the consumer publishes straight to the tier matching the attempt,
+    // so the queue itself does not dead-letter into them
+    for (idx, ttl) in def.retry_backoff.iter().enumerate() {
+        let mut args = BTreeMap::new();
+        args.insert(
+            ShortString::from(AMQP_HEADERS_DEAD_LETTER_EXCHANGE),
+            AMQPValue::LongString(LongString::from("")),
+        );
+        args.insert(
+            ShortString::from(AMQP_HEADERS_DEAD_LETTER_ROUTING_KEY),
+            AMQPValue::LongString(LongString::from(def.name.clone())),
+        );
+        args.insert(
+            ShortString::from(AMQP_HEADERS_MESSAGE_TTL),
+            AMQPValue::LongInt(LongInt::from(*ttl)),
+        );
+
+        declarations.push(QueueDeclaration {
+            name: def.retry_tier_name(idx + 1),
+            options,
+            args,
+        });
+    }
+
+    if let Some(dlq_name) = &def.dlq_name {
+        declarations.push(QueueDeclaration {
+            name: dlq_name.clone(),
+            options,
+            args: BTreeMap::new(),
+        });
+
+        if def.retry_name.is_none() && def.retry_backoff.is_empty() {
+            queue_args.insert(
+                ShortString::from(AMQP_HEADERS_DEAD_LETTER_EXCHANGE),
+                AMQPValue::LongString(LongString::from("")),
+            );
+            queue_args.insert(
+                ShortString::from(AMQP_HEADERS_DEAD_LETTER_ROUTING_KEY),
+                AMQPValue::LongString(LongString::from(def.name.clone())),
+            );
+        }
+    }
+
+    declarations.push(QueueDeclaration {
+        name: def.name.clone(),
+        options,
+        args: queue_args,
+    });
+
+    declarations
+}
+
+fn exchange_options(exch: &ExchangeDefinition, passive: bool) -> ExchangeDeclareOptions {
+    ExchangeDeclareOptions {
+        passive,
+        durable: exch.durable,
+        auto_delete: exch.delete,
+        internal: exch.internal,
+        nowait: exch.no_wait,
+    }
+}
+
+async fn check_channel(conn: &Connection) -> Result<Channel, AmqpError> {
+    match conn.create_channel().await {
+        Err(err) => {
+            error!(error = err.to_string(), "error to create the channel");
+            Err(AmqpError::ChannelError {})
+        }
+        Ok(c) => Ok(c),
+    }
+}
+
+async fn close_check_channel(channel: &Channel) {
+    if let Err(err) = channel.close(200, "OK").await {
+        warn!(error = err.to_string(), "failure to close the channel");
+    }
+}
+
+fn declare_error(name: &str, err: &lapin::Error) -> String {
+    format!("{} - {}", name, err)
+}
+
+/// Broker rejections carry the reason, any other failure is not a topology difference.
+fn declare_failure(err: &lapin::Error) -> Option<String> {
+    match err {
+        lapin::Error::ProtocolError(e) => Some(e.get_message().to_string()),
+        _ => None,
+    }
+}
+
+fn is_not_found(err: &lapin::Error) -> bool {
+    matches!(
+        err,
+        lapin::Error::ProtocolError(e) if *e.kind() == AMQPErrorKind::Soft(AMQPSoftError::NOTFOUND)
+    )
+}
+
+pub(crate) fn queue_arguments(def: &QueueDefinition) -> BTreeMap<ShortString, AMQPValue> {
+    let mut args = BTreeMap::new();
+
+    if def.kind != QueueKind::Classic {
+        args.insert(
+            ShortString::from(AMQP_HEADERS_QUEUE_TYPE),
+            AMQPValue::LongString(LongString::from(def.kind.to_string())),
+        );
+    }
+
+    if let Some(ttl) = def.ttl {
+        args.insert(
+            ShortString::from(AMQP_HEADERS_MESSAGE_TTL),
+            AMQPValue::LongInt(LongInt::from(ttl)),
+        );
+    }
+
+    if let Some(limit) = def.delivery_limit {
+        args.insert(
+            ShortString::from(AMQP_HEADERS_DELIVERY_LIMIT),
+            AMQPValue::LongInt(LongInt::from(limit)),
+        );
+    }
+
+    if let Some(length) = def.max_length {
+        args.insert(
+            ShortString::from(AMQP_HEADERS_MAX_LENGTH),
+            AMQPValue::LongLongInt(LongLongInt::from(length)),
+        );
+    }
+
+    if let Some(bytes) = def.max_length_bytes {
+        args.insert(
+            ShortString::from(AMQP_HEADERS_MAX_LENGTH_BYTES),
+            AMQPValue::LongLongInt(LongLongInt::from(bytes)),
+        );
+    }
+
+    if let Some(overflow) = &def.overflow {
+        args.insert(
+            ShortString::from(AMQP_HEADERS_OVERFLOW),
+            AMQPValue::LongString(LongString::from(overflow.to_string())),
+        );
+    }
+
+    if let Some(priority) = def.max_priority {
+        args.insert(
+            ShortString::from(AMQP_HEADERS_MAX_PRIORITY),
+            AMQPValue::ShortShortUInt(priority),
+        );
+    }
+
+    if def.single_active_consumer {
+        args.insert(
+            ShortString::from(AMQP_HEADERS_SINGLE_ACTIVE_CONSUMER),
+            AMQPValue::Boolean(true),
+        );
+    }
+
+    if def.lazy {
+        args.insert(
+            ShortString::from(AMQP_HEADERS_QUEUE_MODE),
+            AMQPValue::LongString(LongString::from("lazy")),
+        );
+    }
+
+    if let Some(expires) = def.expires {
+        args.insert(
+            ShortString::from(AMQP_HEADERS_EXPIRES),
+            AMQPValue::LongInt(LongInt::from(expires)),
+        );
+    }
+
+    if let Some(age) = &def.max_age {
+        args.insert(
+            ShortString::from(AMQP_HEADERS_MAX_AGE),
+            AMQPValue::LongString(LongString::from(age.clone())),
+        );
+    }
+
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queue::QueueOverflow;
+
+    #[test]
+    fn should_declare_retry_and_dlq_before_the_queue() {
+        let def = QueueDefinition::new("queue")
+            .durable()
+            .with_dlq()
+            .with_retry(1000, 3);
+
+        let declarations = queue_declarations(&def);
+
+        let names: Vec<&str> = declarations.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(names, vec!["queue-retry", "queue-dlq", "queue"]);
+        assert!(declarations.iter().all(|d| d.options.durable));
+        assert_eq!(
+            declarations[0]
+                .args
+                .get(&ShortString::from(AMQP_HEADERS_DEAD_LETTER_ROUTING_KEY)),
+            Some(&AMQPValue::LongString(LongString::from("queue")))
+        );
+        assert_eq!(
+            declarations[2]
+                .args
+                .get(&ShortString::from(AMQP_HEADERS_DEAD_LETTER_ROUTING_KEY)),
+            Some(&AMQPValue::LongString(LongString::from("queue-retry")))
+        );
+    }
+
+    #[test]
+    fn should_declare_backoff_tiers() {
+        let def = QueueDefinition::new("queue")
+            .with_dlq()
+            .with_backoff(&[1000, 60_000]);
+
+        let declarations = queue_declarations(&def);
+
+        let names: Vec<&str> = declarations.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["queue-retry-1", "queue-retry-2", "queue-dlq", "queue"]
+        );
+        assert_eq!(
+            declarations[1]
+                .args
+                .get(&ShortString::from(AMQP_HEADERS_MESSAGE_TTL)),
+            Some(&AMQPValue::LongInt(60_000))
+        );
+        assert!(!declarations[3]
+            .args
+            .contains_key(&ShortString::from(AMQP_HEADERS_DEAD_LETTER_ROUTING_KEY)));
+    }
+
+    #[test]
+    fn should_display_topology_changes() {
+        let change = TopologyChange::QueueMismatch {
+            name: "queue".to_owned(),
+            reason: "inequivalent arg 'x-queue-type'".to_owned(),
+        };
+
+        assert_eq!(
+            change.to_string(),
+            "queue `queue` mismatch - inequivalent arg 'x-queue-type'"
+        );
+    }
+
+    #[test]
+    fn should_not_add_arguments_to_default_queue() {
+        let args = queue_arguments(&QueueDefinition::new("queue"));
+
+        assert!(args.is_empty());
+    }
+
+    #[test]
+    fn should_add_quorum_arguments() {
+        let def = QueueDefinition::new("queue")
+            .quorum()
+            .delivery_limit(3)
+            .single_active_consumer();
+
+        let args = queue_arguments(&def);
+
+        assert_eq!(
+            args.get(&ShortString::from(AMQP_HEADERS_QUEUE_TYPE)),
+            Some(&AMQPValue::LongString(LongString::from("quorum")))
+        );
+        assert_eq!(
+            args.get(&ShortString::from(AMQP_HEADERS_DELIVERY_LIMIT)),
+            Some(&AMQPValue::LongInt(3))
+        );
+        assert_eq!(
+            args.get(&ShortString::from(AMQP_HEADERS_SINGLE_ACTIVE_CONSUMER)),
+            Some(&AMQPValue::Boolean(true))
+        );
+    }
+
+    #[test]
+    fn should_add_stream_arguments() {
+        let def = QueueDefinition::new("events")
+            .stream()
+            .max_age("7D")
+            .max_length_bytes(1_000_000);
+
+        let args = queue_arguments(&def);
+
+        assert_eq!(
+            args.get(&ShortString::from(AMQP_HEADERS_QUEUE_TYPE)),
+            Some(&AMQPValue::LongString(LongString::from("stream")))
+        );
+        assert_eq!(
+            args.get(&ShortString::from(AMQP_HEADERS_MAX_AGE)),
+            Some(&AMQPValue::LongString(LongString::from("7D")))
+        );
+    }
+
+    #[test]
+    fn should_add_classic_arguments() {
+        let def = QueueDefinition::new("queue")
+            .ttl(1000)
+            .max_length(10)
+            .max_length_bytes(1024)
+            .overflow(QueueOverflow::RejectPublish)
+            .max_priority(5)
+            .lazy()
+            .expires(60_000);
+
+        let args = queue_arguments(&def);
+
+        assert_eq!(args.len(), 7);
+        assert_eq!(
+            args.get(&ShortString::from(AMQP_HEADERS_OVERFLOW)),
+            Some(&AMQPValue::LongString(LongString::from("reject-publish")))
+        );
+        assert_eq!(
+            args.get(&ShortString::from(AMQP_HEADERS_MAX_PRIORITY)),
+            Some(&AMQPValue::ShortShortUInt(5))
+        );
+        assert_eq!(
+            args.get(&ShortString::from(AMQP_HEADERS_QUEUE_MODE)),
+            Some(&AMQPValue::LongString(LongString::from("lazy")))
+        );
+    }
+}
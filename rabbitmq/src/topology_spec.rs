@@ -0,0 +1,478 @@
+use crate::{
+    errors::AmqpError,
+    exchange::{ExchangeBinding, ExchangeDefinition, ExchangeKind},
+    queue::{QueueBinding, QueueDefinition, QueueKind, QueueOverflow, StreamOffset},
+    topology::{AmqpTopology, Topology},
+};
+use lapin::{
+    types::{AMQPValue, LongString, ShortString},
+    Channel,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, path::Path, sync::Arc};
+use tracing::{debug, error};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TopologyFormat {
+    Yaml,
+    Toml,
+    Json,
+}
+
+impl TopologyFormat {
+    pub fn from_path(path: &Path) -> Option<TopologyFormat> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "yaml" | "yml" => Some(TopologyFormat::Yaml),
+            "toml" => Some(TopologyFormat::Toml),
+            "json" => Some(TopologyFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ArgumentValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+}
+
+impl From<&ArgumentValue> for AMQPValue {
+    fn from(value: &ArgumentValue) -> Self {
+        match value {
+            ArgumentValue::Bool(v) => AMQPValue::Boolean(*v),
+            ArgumentValue::Int(v) => AMQPValue::LongLongInt(*v),
+            ArgumentValue::Float(v) => AMQPValue::Double(*v),
+            ArgumentValue::String(v) => AMQPValue::LongString(LongString::from(v.clone())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExchangeSpec {
+    pub name: String,
+    pub kind: ExchangeKind,
+    pub durable: bool,
+    pub auto_delete: bool,
+    pub internal: bool,
+    pub arguments: BTreeMap<String, ArgumentValue>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RetrySpec {
+    ///Milliseconds the message waits in the retry queue
+    pub ttl: i32,
+    pub retries: i32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QueueSpec {
+    pub name: String,
+    pub kind: QueueKind,
+    pub durable: bool,
+    pub auto_delete: bool,
+    pub exclusive: bool,
+    pub ttl: Option<i32>,
+    pub dlq: bool,
+    pub retry: Option<RetrySpec>,
+    ///Milliseconds of each retry tier, see `QueueDefinition::with_backoff`
+    pub backoff: Vec<i32>,
+    pub delivery_limit: Option<i32>,
+    pub max_length: Option<i64>,
+    pub max_length_bytes: Option<i64>,
+    pub overflow: Option<QueueOverflow>,
+    pub max_priority: Option<u8>,
+    pub single_active_consumer: bool,
+    pub lazy: bool,
+    pub expires: Option<i32>,
+    pub max_age: Option<String>,
+    pub stream_offset: Option<StreamOffset>,
+    pub prefetch: Option<u16>,
+    pub consumer_tag: Option<String>,
+    pub exclusive_consumer: bool,
+    pub consumer_priority: Option<i32>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QueueBindingSpec {
+    pub queue: String,
+    pub exchange: String,
+    pub routing_key: String,
+    pub arguments: BTreeMap<String, ArgumentValue>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExchangeBindingSpec {
+    pub source: String,
+    pub destination: String,
+    pub routing_key: String,
+    pub arguments: BTreeMap<String, ArgumentValue>,
+}
+
+/// Owned description of a RabbitMQ topology, usually loaded from a YAML, TOML or JSON file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TopologySpec {
+    pub exchanges: Vec<ExchangeSpec>,
+    pub queues: Vec<QueueSpec>,
+    pub queue_bindings: Vec<QueueBindingSpec>,
+    pub exchange_bindings: Vec<ExchangeBindingSpec>,
+}
+
+/// Installer definitions borrowed from a `TopologySpec`.
+pub struct TopologyDefinitions<'td> {
+    pub exchanges: Vec<ExchangeDefinition<'td>>,
+    pub queues: Vec<QueueDefinition>,
+    pub queue_bindings: Vec<QueueBinding<'td>>,
+    pub exchange_bindings: Vec<ExchangeBinding<'td>>,
+}
+
+impl TopologySpec {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<TopologySpec, AmqpError> {
+        let path = path.as_ref();
+
+        let Some(format) = TopologyFormat::from_path(path) else {
+            error!(path = path.to_str(), "unsupported topology file extension");
+            return Err(AmqpError::LoadTopologyError(format!(
+                "unsupported file extension: {}",
+                path.display()
+            )));
+        };
+
+        let content = match fs::read_to_string(path) {
+            Err(err) => {
+                error!(error = err.to_string(), "failure to read topology file");
+                Err(AmqpError::LoadTopologyError(err.to_string()))
+            }
+            Ok(c) => Ok(c),
+        }?;
+
+        TopologySpec::parse(&content, format)
+    }
+
+    pub fn parse(content: &str, format: TopologyFormat) -> Result<TopologySpec, AmqpError> {
+        let spec = match format {
+            TopologyFormat::Yaml => serde_yaml::from_str(content).map_err(|e| e.to_string()),
+            TopologyFormat::Toml => toml::from_str(content).map_err(|e| e.to_string()),
+            TopologyFormat::Json => serde_json::from_str(content).map_err(|e| e.to_string()),
+        };
+
+        match spec {
+            Err(err) => {
+                error!(error = err, "failure to parse topology");
+                Err(AmqpError::LoadTopologyError(err))
+            }
+            Ok(s) => Ok(s),
+        }
+    }
+
+    /// Queue definitions to be registered in the `RabbitMQDispatcher`.
+    pub fn queue_definitions(&self) -> Vec<QueueDefinition> {
+        self.queues.iter().map(QueueDefinition::from).collect()
+    }
+
+    pub fn definitions(&self) -> TopologyDefinitions<'_> {
+        TopologyDefinitions {
+            exchanges: self
+                .exchanges
+                .iter()
+                .map(|exch| {
+                    let mut def = ExchangeDefinition::new(&exch.name)
+                        .kind(&exch.kind)
+                        .params(arguments(&exch.arguments));
+                    if exch.durable {
+                        def = def.durable();
+                    }
+                    if exch.auto_delete {
+                        def = def.delete();
+                    }
+                    if exch.internal {
+                        def = def.internal();
+                    }
+                    def
+                })
+                .collect(),
+            queues: self.queue_definitions(),
+            queue_bindings: self
+                .queue_bindings
+                .iter()
+                .map(|binding| {
+                    QueueBinding::new(&binding.queue)
+                        .exchange(&binding.exchange)
+                        .routing_key(&binding.routing_key)
+                        .params(arguments(&binding.arguments))
+                })
+                .collect(),
+            exchange_bindings: self
+                .exchange_bindings
+                .iter()
+                .map(|binding| {
+                    ExchangeBinding::new(&binding.destination)
+                        .source(&binding.source)
+                        .routing_key(&binding.routing_key)
+                        .params(arguments(&binding.arguments))
+                })
+                .collect(),
+        }
+    }
+
+    pub async fn install(&self, channel: Arc<Channel>) -> Result<(), AmqpError> {
+        debug!("installing topology from spec...");
+        self.definitions().topology(channel).install().await
+    }
+}
+
+impl<'td> TopologyDefinitions<'td> {
+    pub fn topology(&'td self, channel: Arc<Channel>) -> AmqpTopology<'td> {
+        let mut topology = AmqpTopology::new(channel);
+
+        for exch in &self.exchanges {
+            topology = topology.exchange(exch);
+        }
+        for queue in &self.queues {
+            topology = topology.queue(queue);
+        }
+        for binding in &self.exchange_bindings {
+            topology = topology.exchange_binding(binding);
+        }
+        for binding in &self.queue_bindings {
+            topology = topology.queue_binding(binding);
+        }
+
+        topology
+    }
+}
+
+impl From<&QueueSpec> for QueueDefinition {
+    fn from(spec: &QueueSpec) -> Self {
+        let mut def = QueueDefinition::new(&spec.name);
+
+        match spec.kind {
+            QueueKind::Quorum => def = def.quorum(),
+            QueueKind::Stream => def = def.stream(),
+            QueueKind::Classic => {}
+        }
+        if spec.durable {
+            def = def.durable();
+        }
+        if spec.auto_delete {
+            def = def.delete();
+        }
+        if spec.exclusive {
+            def = def.exclusive();
+        }
+        if let Some(ttl) = spec.ttl {
+            def = def.ttl(ttl);
+        }
+        if spec.dlq {
+            def = def.with_dlq();
+        }
+        if let Some(retry) = &spec.retry {
+            def = def.with_retry(retry.ttl, retry.retries);
+        }
+        if !spec.backoff.is_empty() {
+            def = def.with_backoff(&spec.backoff);
+        }
+        if let Some(limit) = spec.delivery_limit {
+            def = def.delivery_limit(limit);
+        }
+        if let Some(length) = spec.max_length {
+            def = def.max_length(length);
+        }
+        if let Some(bytes) = spec.max_length_bytes {
+            def = def.max_length_bytes(bytes);
+        }
+        if let Some(overflow) = &spec.overflow {
+            def = def.overflow(overflow.clone());
+        }
+        if let Some(priority) = spec.max_priority {
+            def = def.max_priority(priority);
+        }
+        if spec.single_active_consumer {
+            def = def.single_active_consumer();
+        }
+        if spec.lazy {
+            def = def.lazy();
+        }
+        if let Some(expires) = spec.expires {
+            def = def.expires(expires);
+        }
+        if let Some(age) = &spec.max_age {
+            def = def.max_age(age);
+        }
+        if let Some(offset) = &spec.stream_offset {
+            def = def.stream_offset(offset.clone());
+        }
+        if let Some(prefetch) = spec.prefetch {
+            def = def.prefetch(prefetch);
+        }
+        if let Some(tag) = &spec.consumer_tag {
+            def = def.consumer_tag(tag);
+        }
+        if spec.exclusive_consumer {
+            def = def.exclusive_consumer();
+        }
+        if let Some(priority) = spec.consumer_priority {
+            def = def.consumer_priority(priority);
+        }
+
+        def
+    }
+}
+
+fn arguments(args: &BTreeMap<String, ArgumentValue>) -> BTreeMap<ShortString, AMQPValue> {
+    args.iter()
+        .map(|(key, value)| (ShortString::from(key.clone()), AMQPValue::from(value)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const YAML: &str = r#"
+exchanges:
+  - name: orders
+    kind: topic
+    durable: true
+  - name: orders-delayed
+    kind: x-delayed-message
+    durable: true
+    arguments:
+      x-delayed-type: direct
+queues:
+  - name: orders-created
+    durable: true
+    dlq: true
+    retry:
+      ttl: 10000
+      retries: 3
+    prefetch: 10
+  - name: orders-audit
+    kind: quorum
+    delivery_limit: 5
+    overflow: reject-publish
+    dlq: true
+    backoff: [1000, 10000, 60000]
+queue_bindings:
+  - queue: orders-created
+    exchange: orders
+    routing_key: orders.created
+exchange_bindings:
+  - source: orders-delayed
+    destination: orders
+    routing_key: orders.created
+"#;
+
+    #[test]
+    fn should_parse_yaml() {
+        let spec = TopologySpec::parse(YAML, TopologyFormat::Yaml).unwrap();
+
+        assert_eq!(spec.exchanges.len(), 2);
+        assert_eq!(spec.exchanges[1].kind, ExchangeKind::XMessageDelayed);
+        assert_eq!(
+            spec.exchanges[1].arguments.get("x-delayed-type"),
+            Some(&ArgumentValue::String("direct".to_owned()))
+        );
+        assert_eq!(spec.queues[1].kind, QueueKind::Quorum);
+        assert_eq!(spec.queues[1].overflow, Some(QueueOverflow::RejectPublish));
+        assert_eq!(spec.queue_bindings[0].routing_key, "orders.created");
+        assert_eq!(spec.exchange_bindings[0].source, "orders-delayed");
+    }
+
+    #[test]
+    fn should_parse_toml() {
+        let content = r#"
+[[exchanges]]
+name = "orders"
+kind = "fanout"
+
+[[queues]]
+name = "orders-created"
+durable = true
+retry = { ttl = 1000, retries = 2 }
+
+[[queue_bindings]]
+queue = "orders-created"
+exchange = "orders"
+"#;
+
+        let spec = TopologySpec::parse(content, TopologyFormat::Toml).unwrap();
+
+        assert_eq!(spec.exchanges[0].kind, ExchangeKind::Fanout);
+        assert_eq!(
+            spec.queues[0].retry,
+            Some(RetrySpec {
+                ttl: 1000,
+                retries: 2
+            })
+        );
+        assert_eq!(spec.queue_bindings[0].routing_key, "");
+    }
+
+    #[test]
+    fn should_parse_json() {
+        let content = r#"{"queues": [
+            {"name": "orders", "max_priority": 10, "lazy": true},
+            {"name": "events", "kind": "stream", "prefetch": 50, "max_age": "7D", "stream_offset": {"offset": 42}}
+        ]}"#;
+
+        let spec = TopologySpec::parse(content, TopologyFormat::Json).unwrap();
+
+        assert_eq!(spec.queues[0].max_priority, Some(10));
+        assert!(spec.queues[0].lazy);
+        assert_eq!(spec.queues[1].stream_offset, Some(StreamOffset::Offset(42)));
+        assert!(QueueDefinition::from(&spec.queues[1]).validate().is_ok());
+    }
+
+    #[test]
+    fn should_reject_unknown_fields() {
+        let content = r#"{"queues": [{"name": "orders", "durabel": true}]}"#;
+
+        assert!(TopologySpec::parse(content, TopologyFormat::Json).is_err());
+    }
+
+    #[test]
+    fn should_detect_format_from_path() {
+        assert_eq!(
+            TopologyFormat::from_path(Path::new("topology.yml")),
+            Some(TopologyFormat::Yaml)
+        );
+        assert_eq!(
+            TopologyFormat::from_path(Path::new("topology.TOML")),
+            Some(TopologyFormat::Toml)
+        );
+        assert_eq!(TopologyFormat::from_path(Path::new("topology")), None);
+    }
+
+    #[test]
+    fn should_convert_into_definitions() {
+        let spec = TopologySpec::parse(YAML, TopologyFormat::Yaml).unwrap();
+
+        let defs = spec.definitions();
+
+        assert_eq!(defs.exchanges[0].name, "orders");
+        assert!(defs.exchanges[0].durable);
+        assert_eq!(
+            defs.queues[0].retry_name,
+            Some("orders-created-retry".to_owned())
+        );
+        assert_eq!(
+            defs.queues[0].dlq_name,
+            Some("orders-created-dlq".to_owned())
+        );
+        assert_eq!(defs.queues[0].prefetch_count, Some(10));
+        assert!(defs.queues[1].durable);
+        assert_eq!(defs.queues[1].retry_backoff, vec![1000, 10_000, 60_000]);
+        assert!(defs.queues.iter().all(|q| q.validate().is_ok()));
+        assert_eq!(defs.queue_bindings[0].exchange_name, "orders");
+        assert_eq!(defs.exchange_bindings[0].destination, "orders");
+    }
+}